@@ -0,0 +1,111 @@
+//! Derive macros for [`fugu`](https://crates.io/crates/fugu).
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Error, Fields, Meta, NestedMeta, Type};
+
+/// Derives `fugu::UniformBlock` for a `#[repr(C)]` struct.
+///
+/// Every field must implement `fugu::UniformField`, or be an array of such
+/// fields marked with `#[uniform(array)]`.
+#[proc_macro_derive(UniformBlock, attributes(uniform))]
+pub fn derive_uniform_block(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+fn expand(input: DeriveInput) -> Result<proc_macro2::TokenStream, Error> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            Fields::Unnamed(fields) => &fields.unnamed,
+            Fields::Unit => {
+                return Err(Error::new(
+                    Span::call_site(),
+                    "UniformBlock cannot be derived for unit structs",
+                ))
+            }
+        },
+        _ => {
+            return Err(Error::new(
+                Span::call_site(),
+                "UniformBlock can only be derived for structs",
+            ))
+        }
+    };
+
+    let mut repr_c = false;
+    for attr in &input.attrs {
+        if attr.path.is_ident("repr") {
+            if let Meta::List(list) = attr.parse_meta()? {
+                repr_c |= list.nested.iter().any(|nested| match nested {
+                    NestedMeta::Meta(meta) => {
+                        meta.path().is_ident("C") || meta.path().is_ident("transparent")
+                    }
+                    NestedMeta::Lit(_) => false,
+                });
+            }
+        }
+    }
+    if !repr_c {
+        return Err(Error::new(
+            Span::call_site(),
+            "UniformBlock can only be derived for #[repr(C)] structs",
+        ));
+    }
+
+    let mut layout = Vec::new();
+    for field in fields {
+        let mut array = false;
+        for attr in &field.attrs {
+            if attr.path.is_ident("uniform") {
+                let list = match attr.parse_meta()? {
+                    Meta::List(list) => list,
+                    meta => return Err(Error::new_spanned(meta, "expected #[uniform(...)]")),
+                };
+                for nested in &list.nested {
+                    match nested {
+                        NestedMeta::Meta(Meta::Path(path)) if path.is_ident("array") => {
+                            array = true;
+                        }
+                        _ => return Err(Error::new_spanned(nested, "unknown uniform attribute")),
+                    }
+                }
+            }
+        }
+
+        let ty = &field.ty;
+        if array {
+            match ty {
+                Type::Array(array) => {
+                    let elem = &array.elem;
+                    let len = &array.len;
+                    layout.push(quote! {
+                        (<#elem as ::fugu::UniformField>::FORMAT, #len)
+                    });
+                }
+                _ => {
+                    return Err(Error::new_spanned(
+                        ty,
+                        "#[uniform(array)] fields must be arrays",
+                    ))
+                }
+            }
+        } else {
+            layout.push(quote! {
+                (<#ty as ::fugu::UniformField>::FORMAT, 1)
+            });
+        }
+    }
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        unsafe impl #impl_generics ::fugu::UniformBlock for #name #ty_generics #where_clause {
+            const LAYOUT: &'static [(::fugu::UniformFormat, usize)] = &[#(#layout),*];
+        }
+    })
+}
@@ -64,12 +64,14 @@ fn main() {
         &[Uniform {
             name: "time",
             format: UniformFormat::Float1,
+            count: 1,
         }],
         &[ImageUniform { name: "tex" }],
+        &[],
     );
 
     let pipeline = ctx.create_pipeline(
-        shader,
+        &shader,
         &[BufferLayout::default()],
         &[
             VertexAttribute {
@@ -88,6 +90,7 @@ fn main() {
                 buffer_index: 0,
             },
         ],
+        PipelineState::default(),
     );
 
     let verts = &[
@@ -122,14 +125,19 @@ fn main() {
         let data = image.to_rgba8();
         let (width, height) = data.dimensions();
         ctx.create_image_with_data(
+            ImageKind::D2,
             width,
             height,
             ImageFormat::Rgba8,
-            ImageFilter::Linear,
-            ImageWrap::Repeat,
+            1,
             data.as_bytes(),
         )
     };
+    let pattern_sampler = ctx.create_sampler(SamplerState {
+        wrap_u: ImageWrap::Repeat,
+        wrap_v: ImageWrap::Repeat,
+        ..SamplerState::default()
+    });
 
     let start_time = Instant::now();
 
@@ -137,6 +145,7 @@ fn main() {
         *control_flow = ControlFlow::Poll;
 
         match event {
+            #[allow(clippy::needless_return)]
             Event::LoopDestroyed => return,
             Event::WindowEvent { event, .. } => match event {
                 WindowEvent::Resized(physical_size) => {
@@ -163,7 +172,7 @@ fn main() {
                 ctx.set_vertex_buffer(&vert_buffer);
                 ctx.set_index_buffer(&idx_buffer);
                 ctx.set_uniforms(start_time.elapsed().as_secs_f32());
-                ctx.set_images(&[&pattern_tex]);
+                ctx.set_images(&[(&pattern_tex, &pattern_sampler)]);
 
                 ctx.draw(0, 6, 1);
 
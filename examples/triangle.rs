@@ -46,10 +46,10 @@ fn main() {
         }
     ";
 
-    let shader = ctx.create_shader(vert_source, frag_source, &[], &[]);
+    let shader = ctx.create_shader(vert_source, frag_source, &[], &[], &[]);
 
     let pipeline = ctx.create_pipeline(
-        shader,
+        &shader,
         &[BufferLayout::default()],
         &[
             VertexAttribute {
@@ -63,6 +63,7 @@ fn main() {
                 buffer_index: 0,
             },
         ],
+        PipelineState::default(),
     );
 
     let verts = &[
@@ -86,6 +87,7 @@ fn main() {
         *control_flow = ControlFlow::Poll;
 
         match event {
+            #[allow(clippy::needless_return)]
             Event::LoopDestroyed => return,
             Event::WindowEvent { event, .. } => match event {
                 WindowEvent::Resized(physical_size) => {
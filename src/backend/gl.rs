@@ -0,0 +1,1610 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cell::{Cell, RefCell};
+use core::{ffi, mem, slice, str};
+
+use glow::HasContext;
+
+use super::{Backend, BufferId, ImageDesc, ImageId, PassId, SamplerId, ShaderId, Slots};
+use crate::{
+    BlendFactor, BlendOp, BufferKind, BufferUsage, CompareFunc, CullMode, Error, FrontFace,
+    ImageFilter, ImageFormat, ImageKind, ImageUniform, ImageWrap, IndexType, PassAction, Pipeline,
+    PipelineInternal, PrimitiveType, SamplerState, ShaderDiagnostic, ShaderStage, StencilOp,
+    Uniform, UniformBlockBinding, UniformFormat, VertexAttributeInternal, VertexFormat,
+};
+
+struct GlBuffer {
+    inner: glow::Buffer,
+    target: u32,
+    usage: u32,
+    size: usize,
+}
+
+struct GlImage {
+    inner: glow::Texture,
+    target: u32,
+    format: u32,
+    kind: u32,
+}
+
+struct GlUniform {
+    location: glow::UniformLocation,
+    format: UniformFormat,
+    count: usize,
+}
+
+struct GlShader {
+    inner: glow::Program,
+    uniforms: Vec<GlUniform>,
+    image_count: usize,
+}
+
+struct GlPass {
+    inner: glow::Framebuffer,
+    width: u32,
+    height: u32,
+}
+
+/// The buffer, size, type, stride and offset of a vertex attribute pointer.
+type GlAttribPointer = (glow::Buffer, i32, u32, i32, i32);
+
+#[derive(Default)]
+struct GlAttrib {
+    enabled: bool,
+    pointer: Option<GlAttribPointer>,
+    divisor: Option<u32>,
+}
+
+/// A shadow copy of the GL state set by the backend, used to skip redundant
+/// GL calls.
+///
+/// `None` means the value is unknown, so the next call always goes through.
+/// Bindings are only skipped when the same object is known to be bound, and
+/// entries referring to destroyed objects are forgotten, since GL may reuse
+/// their names.
+#[derive(Default)]
+struct GlState {
+    program: Option<glow::Program>,
+    array_buffer: Option<glow::Buffer>,
+    element_array_buffer: Option<glow::Buffer>,
+    uniform_buffer: Option<glow::Buffer>,
+    uniform_ranges: Vec<Option<(glow::Buffer, usize, usize)>>,
+    active_texture: Option<u32>,
+    textures: Vec<Option<(u32, glow::Texture)>>,
+    samplers: Vec<Option<glow::Sampler>>,
+    attribs: Vec<GlAttrib>,
+    depth_test: Option<bool>,
+    depth_func: Option<u32>,
+    depth_mask: Option<bool>,
+    stencil_test: Option<bool>,
+    stencil_func: [Option<(u32, i32, u32)>; 2],
+    stencil_op: [Option<(u32, u32, u32)>; 2],
+    stencil_mask: Option<u32>,
+    cull_face_enabled: Option<bool>,
+    cull_face: Option<u32>,
+    front_face: Option<u32>,
+    polygon_offset_fill: Option<bool>,
+    polygon_offset: Option<(f32, f32)>,
+    blend: Option<bool>,
+    blend_equation: Option<(u32, u32)>,
+    blend_func: Option<(u32, u32, u32, u32)>,
+    blend_color: Option<(f32, f32, f32, f32)>,
+    viewport: Option<[i32; 4]>,
+}
+
+/// Records `value` in `cache`, returning whether it changed.
+fn changed<T: PartialEq>(cache: &mut Option<T>, value: T) -> bool {
+    if cache.as_ref() == Some(&value) {
+        false
+    } else {
+        *cache = Some(value);
+        true
+    }
+}
+
+/// Records a binding in `cache`, returning whether it must be issued.
+/// Unbinding is always issued, since `None` also means unknown.
+fn rebind<T: PartialEq + Copy>(cache: &mut Option<T>, value: Option<T>) -> bool {
+    if value.is_some() && *cache == value {
+        false
+    } else {
+        *cache = value;
+        true
+    }
+}
+
+impl GlState {
+    fn forget_program(&mut self, program: glow::Program) {
+        if self.program == Some(program) {
+            self.program = None;
+        }
+    }
+
+    fn forget_buffer(&mut self, buffer: glow::Buffer) {
+        for cache in [
+            &mut self.array_buffer,
+            &mut self.element_array_buffer,
+            &mut self.uniform_buffer,
+        ] {
+            if *cache == Some(buffer) {
+                *cache = None;
+            }
+        }
+        for range in &mut self.uniform_ranges {
+            if matches!(range, Some((inner, ..)) if *inner == buffer) {
+                *range = None;
+            }
+        }
+        for attrib in &mut self.attribs {
+            if matches!(attrib.pointer, Some((inner, ..)) if inner == buffer) {
+                attrib.pointer = None;
+            }
+        }
+    }
+
+    fn forget_texture(&mut self, texture: glow::Texture) {
+        for unit in &mut self.textures {
+            if matches!(unit, Some((_, inner)) if *inner == texture) {
+                *unit = None;
+            }
+        }
+    }
+
+    fn forget_sampler(&mut self, sampler: glow::Sampler) {
+        for unit in &mut self.samplers {
+            if *unit == Some(sampler) {
+                *unit = None;
+            }
+        }
+    }
+}
+
+/// Returns the entry for `index` in `vec`, growing it as needed.
+fn entry<T: Default>(vec: &mut Vec<T>, index: usize) -> &mut T {
+    if vec.len() <= index {
+        vec.resize_with(index + 1, T::default);
+    }
+    &mut vec[index]
+}
+
+/// Records binding `texture` to `unit`, returning whether the unit must be
+/// made active and whether the texture must be bound. The unit is made active
+/// even if the texture is already bound to it, since uploads and mipmap
+/// generation act on the active unit.
+fn select_texture<T: PartialEq + Copy>(
+    active: &mut Option<u32>,
+    units: &mut Vec<Option<T>>,
+    unit: u32,
+    texture: T,
+) -> (bool, bool) {
+    let activate = changed(active, unit);
+    let bind = rebind(entry(units, unit as usize), Some(texture));
+    (activate, bind)
+}
+
+/// An OpenGL backend built on [`glow`].
+pub(crate) struct GlBackend {
+    gl: glow::Context,
+    default_framebuffer: Option<glow::Framebuffer>,
+    framebuffer: Cell<Option<glow::Framebuffer>>,
+    read_framebuffer: glow::Framebuffer,
+    uniform_buffer_offset_alignment: usize,
+    max_anisotropy: f32,
+    buffers: RefCell<Slots<GlBuffer>>,
+    images: RefCell<Slots<GlImage>>,
+    samplers: RefCell<Slots<glow::Sampler>>,
+    shaders: RefCell<Slots<GlShader>>,
+    passes: RefCell<Slots<GlPass>>,
+    saved_viewport: Cell<Option<[i32; 4]>>,
+    state: RefCell<GlState>,
+}
+
+impl GlBackend {
+    pub fn new<F>(loader_function: F) -> Self
+    where
+        F: FnMut(&str) -> *const ffi::c_void,
+    {
+        let gl = unsafe { glow::Context::from_loader_function(loader_function) };
+        let read_framebuffer = unsafe {
+            let vao = gl.create_vertex_array().unwrap();
+            gl.bind_vertex_array(Some(vao));
+            gl.pixel_store_i32(glow::PACK_ALIGNMENT, 1);
+            gl.pixel_store_i32(glow::UNPACK_ALIGNMENT, 1);
+            gl.create_framebuffer().unwrap()
+        };
+
+        // TODO: fix this (blocked by grovesNL/glow#187)
+        let default_framebuffer = unsafe {
+            mem::transmute::<u32, Option<glow::Framebuffer>>(
+                gl.get_parameter_i32(glow::FRAMEBUFFER_BINDING) as u32,
+            )
+        };
+
+        let uniform_buffer_offset_alignment =
+            unsafe { gl.get_parameter_i32(glow::UNIFORM_BUFFER_OFFSET_ALIGNMENT) as usize }.max(1);
+
+        let extensions = gl.supported_extensions();
+        let max_anisotropy = if extensions.contains("GL_EXT_texture_filter_anisotropic")
+            || extensions.contains("GL_ARB_texture_filter_anisotropic")
+        {
+            unsafe { gl.get_parameter_f32(glow::MAX_TEXTURE_MAX_ANISOTROPY_EXT) }
+        } else {
+            1.0
+        };
+
+        Self {
+            gl,
+            default_framebuffer,
+            framebuffer: Cell::new(default_framebuffer),
+            read_framebuffer,
+            uniform_buffer_offset_alignment,
+            max_anisotropy,
+            buffers: RefCell::new(Slots::new()),
+            images: RefCell::new(Slots::new()),
+            samplers: RefCell::new(Slots::new()),
+            shaders: RefCell::new(Slots::new()),
+            passes: RefCell::new(Slots::new()),
+            saved_viewport: Cell::new(None),
+            state: RefCell::new(GlState::default()),
+        }
+    }
+}
+
+impl Backend for GlBackend {
+    fn create_buffer(
+        &self,
+        kind: BufferKind,
+        usage: BufferUsage,
+        size: usize,
+        data: Option<&[u8]>,
+    ) -> Result<BufferId, Error> {
+        let target = match kind {
+            BufferKind::Vertex => glow::ARRAY_BUFFER,
+            BufferKind::Index => glow::ELEMENT_ARRAY_BUFFER,
+            BufferKind::Uniform => glow::UNIFORM_BUFFER,
+        };
+        let usage = match usage {
+            BufferUsage::Static => glow::STATIC_DRAW,
+            BufferUsage::Dynamic => glow::DYNAMIC_DRAW,
+            BufferUsage::Stream => glow::STREAM_DRAW,
+        };
+
+        let inner = unsafe {
+            let buffer = self.gl.create_buffer().map_err(|_| Error::OutOfMemory)?;
+            self.bind_buffer(target, Some(buffer));
+            match data {
+                Some(data) => self.gl.buffer_data_u8_slice(target, data, usage),
+                None => self.gl.buffer_data_size(target, size as _, usage),
+            }
+            buffer
+        };
+
+        let id = self.buffers.borrow_mut().insert(GlBuffer {
+            inner,
+            target,
+            usage,
+            size,
+        });
+        Ok(BufferId(id))
+    }
+
+    fn update_buffer(&self, buffer: BufferId, offset: usize, data: &[u8]) {
+        let buffers = self.buffers.borrow();
+        let buffer = buffers.get(buffer.0);
+        unsafe {
+            self.bind_buffer(buffer.target, Some(buffer.inner));
+            self.gl
+                .buffer_sub_data_u8_slice(buffer.target, offset as _, data);
+        }
+    }
+
+    fn invalidate_buffer(&self, buffer: BufferId) {
+        let buffers = self.buffers.borrow();
+        let buffer = buffers.get(buffer.0);
+        unsafe {
+            // Respecifying the storage orphans the old one, which the driver
+            // frees once the GPU is done with it.
+            self.bind_buffer(buffer.target, Some(buffer.inner));
+            self.gl
+                .buffer_data_size(buffer.target, buffer.size as _, buffer.usage);
+        }
+    }
+
+    fn destroy_buffer(&self, buffer: BufferId) {
+        let buffer = self.buffers.borrow_mut().remove(buffer.0);
+        self.state.borrow_mut().forget_buffer(buffer.inner);
+        unsafe {
+            self.gl.delete_buffer(buffer.inner);
+        }
+    }
+
+    fn create_image(&self, desc: &ImageDesc, data: Option<&[u8]>) -> Result<ImageId, Error> {
+        let (internal_format, format, kind) = gl_image_format(desc.format);
+        let target = gl_image_target(desc.kind);
+
+        let inner = unsafe {
+            let texture = self.gl.create_texture().map_err(|_| Error::OutOfMemory)?;
+            self.bind_texture(0, target, texture);
+
+            for level in 0..desc.mip_count {
+                let (width, height, layers) = desc.level_size(level);
+                let data = data.filter(|_| level == 0);
+                match desc.kind {
+                    ImageKind::D2 => self.gl.tex_image_2d(
+                        target,
+                        level as _,
+                        internal_format as _,
+                        width as _,
+                        height as _,
+                        0,
+                        format,
+                        kind,
+                        data,
+                    ),
+                    ImageKind::Cube => {
+                        for face in 0..layers {
+                            let data = data.map(|data| {
+                                let len = data.len() / layers as usize;
+                                &data[face as usize * len..][..len]
+                            });
+                            self.gl.tex_image_2d(
+                                glow::TEXTURE_CUBE_MAP_POSITIVE_X + face,
+                                level as _,
+                                internal_format as _,
+                                width as _,
+                                height as _,
+                                0,
+                                format,
+                                kind,
+                                data,
+                            );
+                        }
+                    }
+                    ImageKind::Array { .. } | ImageKind::D3 { .. } => self.gl.tex_image_3d(
+                        target,
+                        level as _,
+                        internal_format as _,
+                        width as _,
+                        height as _,
+                        layers as _,
+                        0,
+                        format,
+                        kind,
+                        data,
+                    ),
+                }
+            }
+            self.gl
+                .tex_parameter_i32(target, glow::TEXTURE_MAX_LEVEL, desc.mip_count as i32 - 1);
+
+            texture
+        };
+
+        let id = self.images.borrow_mut().insert(GlImage {
+            inner,
+            target,
+            format,
+            kind,
+        });
+        Ok(ImageId(id))
+    }
+
+    fn update_image(
+        &self,
+        image: ImageId,
+        level: u32,
+        layer: u32,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+        data: &[u8],
+    ) {
+        let images = self.images.borrow();
+        let image = images.get(image.0);
+        unsafe {
+            self.bind_texture(0, image.target, image.inner);
+            match image.target {
+                glow::TEXTURE_2D | glow::TEXTURE_CUBE_MAP => {
+                    let target = if image.target == glow::TEXTURE_CUBE_MAP {
+                        glow::TEXTURE_CUBE_MAP_POSITIVE_X + layer
+                    } else {
+                        image.target
+                    };
+                    self.gl.tex_sub_image_2d(
+                        target,
+                        level as _,
+                        x as _,
+                        y as _,
+                        width as _,
+                        height as _,
+                        image.format,
+                        image.kind,
+                        glow::PixelUnpackData::Slice(data),
+                    );
+                }
+                _ => self.gl.tex_sub_image_3d(
+                    image.target,
+                    level as _,
+                    x as _,
+                    y as _,
+                    layer as _,
+                    width as _,
+                    height as _,
+                    1,
+                    image.format,
+                    image.kind,
+                    glow::PixelUnpackData::Slice(data),
+                ),
+            }
+        }
+    }
+
+    fn generate_mipmaps(&self, image: ImageId) {
+        let images = self.images.borrow();
+        let image = images.get(image.0);
+        unsafe {
+            self.bind_texture(0, image.target, image.inner);
+            self.gl.generate_mipmap(image.target);
+        }
+    }
+
+    fn read_image(&self, image: ImageId, x: u32, y: u32, width: u32, height: u32, buf: &mut [u8]) {
+        let images = self.images.borrow();
+        let image = images.get(image.0);
+        let attachment = match image.format {
+            glow::DEPTH_COMPONENT => glow::DEPTH_ATTACHMENT,
+            glow::DEPTH_STENCIL => glow::DEPTH_STENCIL_ATTACHMENT,
+            _ => glow::COLOR_ATTACHMENT0,
+        };
+        unsafe {
+            self.gl
+                .bind_framebuffer(glow::READ_FRAMEBUFFER, Some(self.read_framebuffer));
+            match image.target {
+                glow::TEXTURE_2D => self.gl.framebuffer_texture_2d(
+                    glow::READ_FRAMEBUFFER,
+                    attachment,
+                    glow::TEXTURE_2D,
+                    Some(image.inner),
+                    0,
+                ),
+                glow::TEXTURE_CUBE_MAP => self.gl.framebuffer_texture_2d(
+                    glow::READ_FRAMEBUFFER,
+                    attachment,
+                    glow::TEXTURE_CUBE_MAP_POSITIVE_X,
+                    Some(image.inner),
+                    0,
+                ),
+                _ => self.gl.framebuffer_texture_layer(
+                    glow::READ_FRAMEBUFFER,
+                    attachment,
+                    Some(image.inner),
+                    0,
+                    0,
+                ),
+            }
+            self.gl.read_pixels(
+                x as _,
+                y as _,
+                width as _,
+                height as _,
+                image.format,
+                image.kind,
+                glow::PixelPackData::Slice(buf),
+            );
+            self.gl
+                .framebuffer_texture_layer(glow::READ_FRAMEBUFFER, attachment, None, 0, 0);
+            self.gl
+                .bind_framebuffer(glow::READ_FRAMEBUFFER, self.framebuffer.get());
+        }
+    }
+
+    fn destroy_image(&self, image: ImageId) {
+        let image = self.images.borrow_mut().remove(image.0);
+        self.state.borrow_mut().forget_texture(image.inner);
+        unsafe {
+            self.gl.delete_texture(image.inner);
+        }
+    }
+
+    fn create_sampler(&self, state: &SamplerState) -> Result<SamplerId, Error> {
+        let (r, g, b, a) = state.border_color;
+
+        let inner = unsafe {
+            let sampler = self.gl.create_sampler().map_err(|_| Error::OutOfMemory)?;
+
+            self.gl.sampler_parameter_i32(
+                sampler,
+                glow::TEXTURE_MIN_FILTER,
+                gl_min_filter(state.min_filter, state.mipmap_filter) as _,
+            );
+            self.gl.sampler_parameter_i32(
+                sampler,
+                glow::TEXTURE_MAG_FILTER,
+                gl_image_filter(state.mag_filter) as _,
+            );
+            self.gl.sampler_parameter_i32(
+                sampler,
+                glow::TEXTURE_WRAP_S,
+                gl_image_wrap(state.wrap_u) as _,
+            );
+            self.gl.sampler_parameter_i32(
+                sampler,
+                glow::TEXTURE_WRAP_T,
+                gl_image_wrap(state.wrap_v) as _,
+            );
+            self.gl.sampler_parameter_i32(
+                sampler,
+                glow::TEXTURE_WRAP_R,
+                gl_image_wrap(state.wrap_w) as _,
+            );
+            self.gl
+                .sampler_parameter_f32_slice(sampler, glow::TEXTURE_BORDER_COLOR, &[r, g, b, a]);
+            self.gl
+                .sampler_parameter_f32(sampler, glow::TEXTURE_LOD_BIAS, state.lod_bias);
+            self.gl
+                .sampler_parameter_f32(sampler, glow::TEXTURE_MIN_LOD, state.min_lod);
+            self.gl
+                .sampler_parameter_f32(sampler, glow::TEXTURE_MAX_LOD, state.max_lod);
+            if self.max_anisotropy > 1.0 {
+                self.gl.sampler_parameter_f32(
+                    sampler,
+                    glow::TEXTURE_MAX_ANISOTROPY_EXT,
+                    (state.max_anisotropy as f32).min(self.max_anisotropy),
+                );
+            }
+            match state.compare {
+                Some(func) => {
+                    self.gl.sampler_parameter_i32(
+                        sampler,
+                        glow::TEXTURE_COMPARE_MODE,
+                        glow::COMPARE_REF_TO_TEXTURE as _,
+                    );
+                    self.gl.sampler_parameter_i32(
+                        sampler,
+                        glow::TEXTURE_COMPARE_FUNC,
+                        gl_compare_func(func) as _,
+                    );
+                }
+                None => self.gl.sampler_parameter_i32(
+                    sampler,
+                    glow::TEXTURE_COMPARE_MODE,
+                    glow::NONE as _,
+                ),
+            }
+
+            sampler
+        };
+
+        let id = self.samplers.borrow_mut().insert(inner);
+        Ok(SamplerId(id))
+    }
+
+    fn destroy_sampler(&self, sampler: SamplerId) {
+        let sampler = self.samplers.borrow_mut().remove(sampler.0);
+        self.state.borrow_mut().forget_sampler(sampler);
+        unsafe {
+            self.gl.delete_sampler(sampler);
+        }
+    }
+
+    fn create_shader(
+        &self,
+        vert_source: &[u8],
+        frag_source: &[u8],
+        uniforms: &[Uniform],
+        images: &[ImageUniform],
+        uniform_blocks: &[UniformBlockBinding],
+    ) -> Result<ShaderId, Error> {
+        let inner = unsafe {
+            let vert = self.compile_shader(ShaderStage::Vertex, vert_source)?;
+            let frag = match self.compile_shader(ShaderStage::Fragment, frag_source) {
+                Ok(frag) => frag,
+                Err(err) => {
+                    self.gl.delete_shader(vert);
+                    return Err(err);
+                }
+            };
+
+            let program = match self.gl.create_program() {
+                Ok(program) => program,
+                Err(_) => {
+                    self.gl.delete_shader(vert);
+                    self.gl.delete_shader(frag);
+                    return Err(Error::OutOfMemory);
+                }
+            };
+
+            self.gl.attach_shader(program, vert);
+            self.gl.attach_shader(program, frag);
+
+            self.gl.link_program(program);
+
+            self.gl.detach_shader(program, vert);
+            self.gl.detach_shader(program, frag);
+            self.gl.delete_shader(vert);
+            self.gl.delete_shader(frag);
+
+            if !self.gl.get_program_link_status(program) {
+                let log = self.gl.get_program_info_log(program);
+                self.gl.delete_program(program);
+                return Err(Error::ShaderLink {
+                    diagnostics: parse_info_log(&log),
+                });
+            }
+
+            program
+        };
+
+        let resolve = || -> Result<GlShader, Error> {
+            let mut shader = GlShader {
+                inner,
+                uniforms: Vec::with_capacity(uniforms.len()),
+                image_count: images.len(),
+            };
+
+            for uniform in uniforms {
+                shader.uniforms.push(GlUniform {
+                    location: unsafe { self.gl.get_uniform_location(inner, uniform.name) }
+                        .ok_or(Error::MissingUniform { name: uniform.name })?,
+                    format: uniform.format,
+                    count: uniform.count,
+                });
+            }
+
+            // Image uniforms always sample from the texture unit matching their
+            // index, so they only need to be set once.
+            self.use_program(inner);
+            for (i, image_uniform) in images.iter().enumerate() {
+                let location = unsafe { self.gl.get_uniform_location(inner, image_uniform.name) }
+                    .ok_or(Error::MissingUniform {
+                    name: image_uniform.name,
+                })?;
+                unsafe {
+                    self.gl.uniform_1_i32(Some(&location), i as i32);
+                }
+            }
+
+            for block in uniform_blocks {
+                unsafe {
+                    let index = self
+                        .gl
+                        .get_uniform_block_index(inner, block.name)
+                        .ok_or(Error::MissingUniformBlock { name: block.name })?;
+                    self.gl.uniform_block_binding(inner, index, block.binding);
+                }
+            }
+
+            Ok(shader)
+        };
+
+        match resolve() {
+            Ok(shader) => Ok(ShaderId(self.shaders.borrow_mut().insert(shader))),
+            Err(err) => {
+                self.state.borrow_mut().forget_program(inner);
+                unsafe {
+                    self.gl.delete_program(inner);
+                }
+                Err(err)
+            }
+        }
+    }
+
+    fn attrib_location(&self, shader: ShaderId, name: &str) -> Option<u32> {
+        let shaders = self.shaders.borrow();
+        unsafe {
+            self.gl
+                .get_attrib_location(shaders.get(shader.0).inner, name)
+        }
+    }
+
+    fn destroy_shader(&self, shader: ShaderId) {
+        let shader = self.shaders.borrow_mut().remove(shader.0);
+        self.state.borrow_mut().forget_program(shader.inner);
+        unsafe {
+            self.gl.delete_program(shader.inner);
+        }
+    }
+
+    fn create_pass(
+        &self,
+        color: &[ImageId],
+        depth_stencil: Option<ImageId>,
+        width: u32,
+        height: u32,
+    ) -> Result<PassId, Error> {
+        let images = self.images.borrow();
+        let inner = unsafe {
+            let framebuffer = self
+                .gl
+                .create_framebuffer()
+                .map_err(|_| Error::OutOfMemory)?;
+            self.gl
+                .bind_framebuffer(glow::FRAMEBUFFER, Some(framebuffer));
+
+            let mut draw_buffers = Vec::with_capacity(color.len());
+            for (i, image) in color.iter().enumerate() {
+                let attachment = glow::COLOR_ATTACHMENT0 + i as u32;
+                self.gl.framebuffer_texture_2d(
+                    glow::FRAMEBUFFER,
+                    attachment,
+                    glow::TEXTURE_2D,
+                    Some(images.get(image.0).inner),
+                    0,
+                );
+                draw_buffers.push(attachment);
+            }
+            if draw_buffers.is_empty() {
+                // Depth-only passes have no color buffer to draw to or read from.
+                self.gl.draw_buffers(&[glow::NONE]);
+                self.gl.read_buffer(glow::NONE);
+            } else {
+                self.gl.draw_buffers(&draw_buffers);
+            }
+
+            if let Some(image) = depth_stencil {
+                let image = images.get(image.0);
+                let attachment = if image.format == glow::DEPTH_STENCIL {
+                    glow::DEPTH_STENCIL_ATTACHMENT
+                } else {
+                    glow::DEPTH_ATTACHMENT
+                };
+                self.gl.framebuffer_texture_2d(
+                    glow::FRAMEBUFFER,
+                    attachment,
+                    glow::TEXTURE_2D,
+                    Some(image.inner),
+                    0,
+                );
+            }
+
+            let status = self.gl.check_framebuffer_status(glow::FRAMEBUFFER);
+            self.gl
+                .bind_framebuffer(glow::FRAMEBUFFER, self.framebuffer.get());
+            if status != glow::FRAMEBUFFER_COMPLETE {
+                self.gl.delete_framebuffer(framebuffer);
+                return Err(Error::IncompleteFramebuffer { status });
+            }
+
+            framebuffer
+        };
+
+        let id = self.passes.borrow_mut().insert(GlPass {
+            inner,
+            width,
+            height,
+        });
+        Ok(PassId(id))
+    }
+
+    fn destroy_pass(&self, pass: PassId) {
+        let pass = self.passes.borrow_mut().remove(pass.0);
+        unsafe {
+            self.gl.delete_framebuffer(pass.inner);
+        }
+    }
+
+    fn begin_pass(&self, pass: Option<PassId>, action: PassAction) {
+        unsafe {
+            match pass {
+                Some(pass) => {
+                    let passes = self.passes.borrow();
+                    let pass = passes.get(pass.0);
+                    let viewport = self.state.borrow().viewport;
+                    let viewport = viewport.unwrap_or_else(|| {
+                        let mut viewport = [0; 4];
+                        self.gl
+                            .get_parameter_i32_slice(glow::VIEWPORT, &mut viewport);
+                        viewport
+                    });
+                    self.saved_viewport.set(Some(viewport));
+                    self.framebuffer.set(Some(pass.inner));
+                    self.gl
+                        .bind_framebuffer(glow::FRAMEBUFFER, Some(pass.inner));
+                    self.set_viewport(0, 0, pass.width as _, pass.height as _);
+                }
+                None => {
+                    self.framebuffer.set(self.default_framebuffer);
+                    self.gl
+                        .bind_framebuffer(glow::FRAMEBUFFER, self.default_framebuffer);
+                }
+            }
+
+            match action {
+                PassAction::Nothing => {}
+                PassAction::Clear {
+                    color,
+                    depth,
+                    stencil,
+                } => {
+                    let mut state = self.state.borrow_mut();
+                    // Depth and stencil writes may have been masked by the
+                    // current pipeline, which would also mask the clear, so
+                    // the masks are lifted for the clear and restored after.
+                    let depth_mask = state.depth_mask;
+                    let stencil_mask = state.stencil_mask;
+                    let mut clear_flag = 0;
+                    if let Some((r, g, b, a)) = color {
+                        self.gl.clear_color(r, g, b, a);
+                        clear_flag |= glow::COLOR_BUFFER_BIT;
+                    }
+                    if let Some(depth) = depth {
+                        if changed(&mut state.depth_mask, true) {
+                            self.gl.depth_mask(true);
+                        }
+                        self.gl.clear_depth_f32(depth);
+                        clear_flag |= glow::DEPTH_BUFFER_BIT;
+                    }
+                    if let Some(stencil) = stencil {
+                        if changed(&mut state.stencil_mask, !0) {
+                            self.gl.stencil_mask(!0);
+                        }
+                        self.gl.clear_stencil(stencil);
+                        clear_flag |= glow::STENCIL_BUFFER_BIT;
+                    }
+                    self.gl.clear(clear_flag);
+
+                    if let Some(mask) = depth_mask {
+                        if changed(&mut state.depth_mask, mask) {
+                            self.gl.depth_mask(mask);
+                        }
+                    }
+                    if let Some(mask) = stencil_mask {
+                        if changed(&mut state.stencil_mask, mask) {
+                            self.gl.stencil_mask(mask);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn end_pass(&self) {
+        self.framebuffer.set(self.default_framebuffer);
+        unsafe {
+            self.gl
+                .bind_framebuffer(glow::FRAMEBUFFER, self.default_framebuffer);
+        }
+        if let Some([x, y, width, height]) = self.saved_viewport.take() {
+            self.set_viewport(x, y, width, height);
+        }
+    }
+
+    fn set_viewport(&self, x: i32, y: i32, width: i32, height: i32) {
+        if changed(&mut self.state.borrow_mut().viewport, [x, y, width, height]) {
+            unsafe {
+                self.gl.viewport(x, y, width, height);
+            }
+        }
+    }
+
+    fn read_pixels(&self, x: i32, y: i32, width: i32, height: i32, buf: &mut [u8]) {
+        unsafe {
+            self.gl.read_pixels(
+                x,
+                y,
+                width,
+                height,
+                glow::RGBA,
+                glow::UNSIGNED_BYTE,
+                glow::PixelPackData::Slice(buf),
+            );
+        }
+    }
+
+    fn apply_pipeline(&self, _handle: Pipeline, pipeline: &PipelineInternal) {
+        self.use_program(
+            self.shaders
+                .borrow()
+                .get(pipeline.shader.internal.id.0)
+                .inner,
+        );
+
+        let mut state = self.state.borrow_mut();
+        let state = &mut *state;
+        unsafe {
+            self.set_enabled(
+                &mut state.depth_test,
+                glow::DEPTH_TEST,
+                pipeline.depth.enabled(),
+            );
+            if pipeline.depth.enabled() {
+                let func = gl_compare_func(pipeline.depth.compare);
+                if changed(&mut state.depth_func, func) {
+                    self.gl.depth_func(func);
+                }
+            }
+            if changed(&mut state.depth_mask, pipeline.depth.write) {
+                self.gl.depth_mask(pipeline.depth.write);
+            }
+
+            self.set_enabled(
+                &mut state.stencil_test,
+                glow::STENCIL_TEST,
+                pipeline.stencil.is_some(),
+            );
+            if let Some(stencil) = pipeline.stencil {
+                let faces = [(glow::FRONT, stencil.front), (glow::BACK, stencil.back)];
+                for (i, (face, face_state)) in faces.iter().enumerate() {
+                    let func = (
+                        gl_compare_func(face_state.compare),
+                        stencil.reference as i32,
+                        stencil.read_mask as u32,
+                    );
+                    if changed(&mut state.stencil_func[i], func) {
+                        self.gl.stencil_func_separate(*face, func.0, func.1, func.2);
+                    }
+                    let op = (
+                        gl_stencil_op(face_state.fail_op),
+                        gl_stencil_op(face_state.depth_fail_op),
+                        gl_stencil_op(face_state.pass_op),
+                    );
+                    if changed(&mut state.stencil_op[i], op) {
+                        self.gl.stencil_op_separate(*face, op.0, op.1, op.2);
+                    }
+                }
+                if changed(&mut state.stencil_mask, stencil.write_mask as u32) {
+                    self.gl.stencil_mask(stencil.write_mask as _);
+                }
+            }
+
+            let cull_face = match pipeline.raster.cull {
+                CullMode::None => None,
+                CullMode::Front => Some(glow::FRONT),
+                CullMode::Back => Some(glow::BACK),
+            };
+            self.set_enabled(
+                &mut state.cull_face_enabled,
+                glow::CULL_FACE,
+                cull_face.is_some(),
+            );
+            if let Some(cull_face) = cull_face {
+                if changed(&mut state.cull_face, cull_face) {
+                    self.gl.cull_face(cull_face);
+                }
+            }
+            let front_face = match pipeline.raster.front_face {
+                FrontFace::Ccw => glow::CCW,
+                FrontFace::Cw => glow::CW,
+            };
+            if changed(&mut state.front_face, front_face) {
+                self.gl.front_face(front_face);
+            }
+
+            let depth_bias = pipeline.raster.depth_bias_enabled();
+            self.set_enabled(
+                &mut state.polygon_offset_fill,
+                glow::POLYGON_OFFSET_FILL,
+                depth_bias,
+            );
+            if depth_bias {
+                let offset = (pipeline.raster.depth_bias_slope, pipeline.raster.depth_bias);
+                if changed(&mut state.polygon_offset, offset) {
+                    self.gl.polygon_offset(offset.0, offset.1);
+                }
+            }
+
+            self.set_enabled(&mut state.blend, glow::BLEND, pipeline.blend.is_some());
+            if let Some(color) = pipeline.blend {
+                let alpha = pipeline.alpha_blend.unwrap_or(color);
+                let equation = (gl_blend_op(color.op), gl_blend_op(alpha.op));
+                if changed(&mut state.blend_equation, equation) {
+                    self.gl.blend_equation_separate(equation.0, equation.1);
+                }
+                let func = (
+                    gl_blend_factor(color.source),
+                    gl_blend_factor(color.dest),
+                    gl_blend_factor(alpha.source),
+                    gl_blend_factor(alpha.dest),
+                );
+                if changed(&mut state.blend_func, func) {
+                    self.gl.blend_func_separate(func.0, func.1, func.2, func.3);
+                }
+                if changed(&mut state.blend_color, pipeline.blend_color) {
+                    let (r, g, b, a) = pipeline.blend_color;
+                    self.gl.blend_color(r, g, b, a);
+                }
+            }
+        }
+    }
+
+    fn apply_vertex_buffers(
+        &self,
+        attrs: &[Vec<VertexAttributeInternal>],
+        buffers: &[(BufferId, usize)],
+    ) {
+        let gl_buffers = self.buffers.borrow();
+        for (buffer_index, attrs) in attrs.iter().enumerate() {
+            let (buffer, buffer_offset) = buffers[buffer_index];
+            let buffer = gl_buffers.get(buffer.0).inner;
+            for attr in attrs {
+                let (size, format) = match attr.format {
+                    VertexFormat::Float1 => (1, glow::FLOAT),
+                    VertexFormat::Float2 => (2, glow::FLOAT),
+                    VertexFormat::Float3 => (3, glow::FLOAT),
+                    VertexFormat::Float4 => (4, glow::FLOAT),
+                    VertexFormat::Byte1 => (1, glow::BYTE),
+                    VertexFormat::Byte2 => (2, glow::BYTE),
+                    VertexFormat::Byte3 => (3, glow::BYTE),
+                    VertexFormat::Byte4 => (4, glow::BYTE),
+                    VertexFormat::Short1 => (1, glow::SHORT),
+                    VertexFormat::Short2 => (2, glow::SHORT),
+                    VertexFormat::Short3 => (3, glow::SHORT),
+                    VertexFormat::Short4 => (4, glow::SHORT),
+                };
+                let offset = attr.offset + buffer_offset as i32;
+                let pointer = (buffer, size, format, attr.stride, offset);
+
+                let mut state = self.state.borrow_mut();
+                let cached = entry(&mut state.attribs, attr.location as usize);
+                let enable = !cached.enabled;
+                cached.enabled = true;
+                let update_pointer = changed(&mut cached.pointer, pointer);
+                let update_divisor = changed(&mut cached.divisor, attr.divisor);
+                drop(state);
+
+                unsafe {
+                    if enable {
+                        self.gl.enable_vertex_attrib_array(attr.location);
+                    }
+                    if update_pointer {
+                        // The pointer is sourced from the bound array buffer.
+                        self.bind_buffer(glow::ARRAY_BUFFER, Some(buffer));
+                        self.gl.vertex_attrib_pointer_f32(
+                            attr.location,
+                            size,
+                            format,
+                            false,
+                            attr.stride,
+                            offset,
+                        );
+                    }
+                    if update_divisor {
+                        self.gl.vertex_attrib_divisor(attr.location, attr.divisor);
+                    }
+                }
+            }
+        }
+
+        // Disable attributes left enabled by previous pipelines.
+        let mut state = self.state.borrow_mut();
+        for (location, cached) in state.attribs.iter_mut().enumerate() {
+            let used = attrs
+                .iter()
+                .flatten()
+                .any(|attr| attr.location as usize == location);
+            if cached.enabled && !used {
+                cached.enabled = false;
+                unsafe {
+                    self.gl.disable_vertex_attrib_array(location as u32);
+                }
+            }
+        }
+    }
+
+    fn apply_index_buffer(&self, buffer: BufferId) {
+        let buffers = self.buffers.borrow();
+        self.bind_buffer(
+            glow::ELEMENT_ARRAY_BUFFER,
+            Some(buffers.get(buffer.0).inner),
+        );
+    }
+
+    fn apply_uniforms(&self, shader: ShaderId, data: &[u8]) {
+        let shaders = self.shaders.borrow();
+        let shader = shaders.get(shader.0);
+
+        let mut ptr = data.as_ptr();
+        for uniform in &shader.uniforms {
+            let location = Some(&uniform.location);
+            let len = uniform.format.size() / mem::size_of::<f32>() * uniform.count;
+            unsafe {
+                let floats = slice::from_raw_parts(ptr.cast(), len);
+                let ints = slice::from_raw_parts(ptr.cast(), len);
+                match uniform.format {
+                    UniformFormat::Float1 => self.gl.uniform_1_f32_slice(location, floats),
+                    UniformFormat::Float2 => self.gl.uniform_2_f32_slice(location, floats),
+                    UniformFormat::Float3 => self.gl.uniform_3_f32_slice(location, floats),
+                    UniformFormat::Float4 => self.gl.uniform_4_f32_slice(location, floats),
+                    UniformFormat::Int1 => self.gl.uniform_1_i32_slice(location, ints),
+                    UniformFormat::Int2 => self.gl.uniform_2_i32_slice(location, ints),
+                    UniformFormat::Int3 => self.gl.uniform_3_i32_slice(location, ints),
+                    UniformFormat::Int4 => self.gl.uniform_4_i32_slice(location, ints),
+                    UniformFormat::Mat2 => {
+                        self.gl.uniform_matrix_2_f32_slice(location, false, floats)
+                    }
+                    UniformFormat::Mat3 => {
+                        self.gl.uniform_matrix_3_f32_slice(location, false, floats)
+                    }
+                    UniformFormat::Mat4 => {
+                        self.gl.uniform_matrix_4_f32_slice(location, false, floats)
+                    }
+                }
+                ptr = ptr.add(uniform.format.size() * uniform.count);
+            }
+        }
+    }
+
+    fn apply_uniform_buffer(&self, binding: u32, buffer: BufferId, offset: usize, size: usize) {
+        let buffer = self.buffers.borrow().get(buffer.0).inner;
+        let mut state = self.state.borrow_mut();
+        if rebind(
+            entry(&mut state.uniform_ranges, binding as usize),
+            Some((buffer, offset, size)),
+        ) {
+            // Binding a range also binds the buffer to the generic binding point.
+            state.uniform_buffer = Some(buffer);
+            unsafe {
+                self.gl.bind_buffer_range(
+                    glow::UNIFORM_BUFFER,
+                    binding,
+                    Some(buffer),
+                    offset as _,
+                    size as _,
+                );
+            }
+        }
+    }
+
+    fn apply_images(&self, shader: ShaderId, images: &[(ImageId, SamplerId)]) {
+        let shaders = self.shaders.borrow();
+        let gl_images = self.images.borrow();
+        let samplers = self.samplers.borrow();
+        for (i, &(image, sampler)) in images[..shaders.get(shader.0).image_count]
+            .iter()
+            .enumerate()
+        {
+            let unit = i as u32;
+            let image = gl_images.get(image.0);
+            self.bind_texture(unit, image.target, image.inner);
+
+            let sampler = *samplers.get(sampler.0);
+            if rebind(
+                entry(&mut self.state.borrow_mut().samplers, i),
+                Some(sampler),
+            ) {
+                unsafe {
+                    self.gl.bind_sampler(unit, Some(sampler));
+                }
+            }
+        }
+    }
+
+    fn draw(
+        &self,
+        primitive: PrimitiveType,
+        index_type: Option<IndexType>,
+        start: usize,
+        count: usize,
+        instances: usize,
+    ) {
+        let mode = gl_primitive_type(primitive);
+        unsafe {
+            if let Some(index_type) = index_type {
+                self.gl.draw_elements_instanced(
+                    mode,
+                    count as _,
+                    gl_index_type(index_type),
+                    (start * index_type.size()) as _,
+                    instances as _,
+                );
+            } else {
+                self.gl
+                    .draw_arrays_instanced(mode, start as _, count as _, instances as _);
+            }
+        }
+    }
+
+    fn commit_frame(&self) {
+        self.bind_buffer(glow::ARRAY_BUFFER, None);
+        self.bind_buffer(glow::ELEMENT_ARRAY_BUFFER, None);
+        // TODO: clear texture bindings?
+    }
+
+    fn uniform_buffer_offset_alignment(&self) -> usize {
+        self.uniform_buffer_offset_alignment
+    }
+}
+
+impl GlBackend {
+    fn use_program(&self, program: glow::Program) {
+        if rebind(&mut self.state.borrow_mut().program, Some(program)) {
+            unsafe {
+                self.gl.use_program(Some(program));
+            }
+        }
+    }
+
+    fn bind_buffer(&self, target: u32, buffer: Option<glow::Buffer>) {
+        let mut state = self.state.borrow_mut();
+        let cache = match target {
+            glow::ARRAY_BUFFER => &mut state.array_buffer,
+            glow::ELEMENT_ARRAY_BUFFER => &mut state.element_array_buffer,
+            _ => &mut state.uniform_buffer,
+        };
+        if rebind(cache, buffer) {
+            unsafe {
+                self.gl.bind_buffer(target, buffer);
+            }
+        }
+    }
+
+    fn bind_texture(&self, unit: u32, target: u32, texture: glow::Texture) {
+        let mut state = self.state.borrow_mut();
+        let state = &mut *state;
+        let (activate, bind) = select_texture(
+            &mut state.active_texture,
+            &mut state.textures,
+            unit,
+            (target, texture),
+        );
+        unsafe {
+            if activate {
+                self.gl.active_texture(glow::TEXTURE0 + unit);
+            }
+            if bind {
+                self.gl.bind_texture(target, Some(texture));
+            }
+        }
+    }
+
+    unsafe fn set_enabled(&self, cache: &mut Option<bool>, capability: u32, enabled: bool) {
+        if changed(cache, enabled) {
+            if enabled {
+                self.gl.enable(capability);
+            } else {
+                self.gl.disable(capability);
+            }
+        }
+    }
+
+    unsafe fn compile_shader(
+        &self,
+        stage: ShaderStage,
+        source: &[u8],
+    ) -> Result<glow::Shader, Error> {
+        let source = str::from_utf8(source).map_err(|_| Error::ShaderCompile {
+            stage,
+            diagnostics: vec![ShaderDiagnostic {
+                line: None,
+                column: None,
+                message: "shader source is not valid UTF-8".into(),
+            }],
+        })?;
+
+        let shader = self
+            .gl
+            .create_shader(match stage {
+                ShaderStage::Vertex => glow::VERTEX_SHADER,
+                ShaderStage::Fragment => glow::FRAGMENT_SHADER,
+            })
+            .map_err(|_| Error::OutOfMemory)?;
+
+        self.gl.shader_source(shader, source);
+        self.gl.compile_shader(shader);
+
+        if !self.gl.get_shader_compile_status(shader) {
+            let log = self.gl.get_shader_info_log(shader);
+            self.gl.delete_shader(shader);
+            return Err(Error::ShaderCompile {
+                stage,
+                diagnostics: parse_info_log(&log),
+            });
+        }
+
+        Ok(shader)
+    }
+}
+
+/// Splits a driver info log into per-line diagnostics.
+///
+/// The location prefixes emitted by the common drivers are recognized:
+/// `0:12(5): error: ...` (Mesa), `0(12) : error C0000: ...` (NVIDIA) and
+/// `ERROR: 0:12: ...` (ANGLE, Apple, AMD). Lines in any other format are kept
+/// verbatim without a line number.
+fn parse_info_log(log: &str) -> Vec<ShaderDiagnostic> {
+    log.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            let (severity, rest) = if let Some(rest) = line.strip_prefix("ERROR:") {
+                ("error: ", rest.trim_start())
+            } else if let Some(rest) = line.strip_prefix("WARNING:") {
+                ("warning: ", rest.trim_start())
+            } else {
+                ("", line)
+            };
+
+            match parse_location(rest) {
+                Some((line, column, message)) => ShaderDiagnostic {
+                    line: Some(line),
+                    column,
+                    message: [severity, message].concat(),
+                },
+                None => ShaderDiagnostic {
+                    line: None,
+                    column: None,
+                    message: line.into(),
+                },
+            }
+        })
+        .collect()
+}
+
+/// Parses a `source:line` or `source(line)` location prefix, returning the
+/// line, the column if there is one, and the rest of the message.
+fn parse_location(s: &str) -> Option<(u32, Option<u32>, &str)> {
+    let (_, s) = parse_number(s)?;
+    let (line, column, s) = if let Some(s) = s.strip_prefix(':') {
+        let (line, s) = parse_number(s)?;
+        // Mesa appends the column in parentheses.
+        match s.strip_prefix('(') {
+            Some(s) => {
+                let (column, s) = parse_number(s)?;
+                (line, Some(column), s.strip_prefix(')')?)
+            }
+            None => (line, None, s),
+        }
+    } else {
+        let (line, s) = parse_number(s.strip_prefix('(')?)?;
+        (line, None, s.strip_prefix(')')?.trim_start())
+    };
+    Some((line, column, s.strip_prefix(':')?.trim_start()))
+}
+
+fn parse_number(s: &str) -> Option<(u32, &str)> {
+    let end = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    Some((s[..end].parse().ok()?, &s[end..]))
+}
+
+fn gl_blend_op(op: BlendOp) -> u32 {
+    match op {
+        BlendOp::Add => glow::FUNC_ADD,
+        BlendOp::Subtract => glow::FUNC_SUBTRACT,
+        BlendOp::ReverseSubtract => glow::FUNC_REVERSE_SUBTRACT,
+        BlendOp::Min => glow::MIN,
+        BlendOp::Max => glow::MAX,
+    }
+}
+
+fn gl_blend_factor(factor: BlendFactor) -> u32 {
+    match factor {
+        BlendFactor::Zero => glow::ZERO,
+        BlendFactor::One => glow::ONE,
+        BlendFactor::SourceColor => glow::SRC_COLOR,
+        BlendFactor::OneMinusSourceColor => glow::ONE_MINUS_SRC_COLOR,
+        BlendFactor::SourceAlpha => glow::SRC_ALPHA,
+        BlendFactor::OneMinusSourceAlpha => glow::ONE_MINUS_SRC_ALPHA,
+        BlendFactor::DestColor => glow::DST_COLOR,
+        BlendFactor::OneMinusDestColor => glow::ONE_MINUS_DST_COLOR,
+        BlendFactor::DestAlpha => glow::DST_ALPHA,
+        BlendFactor::OneMinusDestAlpha => glow::ONE_MINUS_DST_ALPHA,
+        BlendFactor::ConstantColor => glow::CONSTANT_COLOR,
+        BlendFactor::OneMinusConstantColor => glow::ONE_MINUS_CONSTANT_COLOR,
+        BlendFactor::ConstantAlpha => glow::CONSTANT_ALPHA,
+        BlendFactor::OneMinusConstantAlpha => glow::ONE_MINUS_CONSTANT_ALPHA,
+        BlendFactor::SourceAlphaSaturate => glow::SRC_ALPHA_SATURATE,
+    }
+}
+
+fn gl_compare_func(func: CompareFunc) -> u32 {
+    match func {
+        CompareFunc::Never => glow::NEVER,
+        CompareFunc::Less => glow::LESS,
+        CompareFunc::Equal => glow::EQUAL,
+        CompareFunc::LessEqual => glow::LEQUAL,
+        CompareFunc::Greater => glow::GREATER,
+        CompareFunc::NotEqual => glow::NOTEQUAL,
+        CompareFunc::GreaterEqual => glow::GEQUAL,
+        CompareFunc::Always => glow::ALWAYS,
+    }
+}
+
+fn gl_stencil_op(op: StencilOp) -> u32 {
+    match op {
+        StencilOp::Keep => glow::KEEP,
+        StencilOp::Zero => glow::ZERO,
+        StencilOp::Replace => glow::REPLACE,
+        StencilOp::IncrementClamp => glow::INCR,
+        StencilOp::DecrementClamp => glow::DECR,
+        StencilOp::Invert => glow::INVERT,
+        StencilOp::IncrementWrap => glow::INCR_WRAP,
+        StencilOp::DecrementWrap => glow::DECR_WRAP,
+    }
+}
+
+fn gl_primitive_type(primitive: PrimitiveType) -> u32 {
+    match primitive {
+        PrimitiveType::Points => glow::POINTS,
+        PrimitiveType::Lines => glow::LINES,
+        PrimitiveType::LineStrip => glow::LINE_STRIP,
+        PrimitiveType::Triangles => glow::TRIANGLES,
+        PrimitiveType::TriangleStrip => glow::TRIANGLE_STRIP,
+    }
+}
+
+fn gl_index_type(ty: IndexType) -> u32 {
+    match ty {
+        IndexType::U8 => glow::UNSIGNED_BYTE,
+        IndexType::U16 => glow::UNSIGNED_SHORT,
+        IndexType::U32 => glow::UNSIGNED_INT,
+    }
+}
+
+fn gl_image_target(kind: ImageKind) -> u32 {
+    match kind {
+        ImageKind::D2 => glow::TEXTURE_2D,
+        ImageKind::Cube => glow::TEXTURE_CUBE_MAP,
+        ImageKind::Array { .. } => glow::TEXTURE_2D_ARRAY,
+        ImageKind::D3 { .. } => glow::TEXTURE_3D,
+    }
+}
+
+fn gl_image_filter(filter: ImageFilter) -> u32 {
+    match filter {
+        ImageFilter::Nearest => glow::NEAREST,
+        ImageFilter::Linear => glow::LINEAR,
+    }
+}
+
+/// Returns the minification filter combining a filter and a mipmap filter.
+fn gl_min_filter(filter: ImageFilter, mipmap_filter: Option<ImageFilter>) -> u32 {
+    match (filter, mipmap_filter) {
+        (filter, None) => gl_image_filter(filter),
+        (ImageFilter::Nearest, Some(ImageFilter::Nearest)) => glow::NEAREST_MIPMAP_NEAREST,
+        (ImageFilter::Linear, Some(ImageFilter::Nearest)) => glow::LINEAR_MIPMAP_NEAREST,
+        (ImageFilter::Nearest, Some(ImageFilter::Linear)) => glow::NEAREST_MIPMAP_LINEAR,
+        (ImageFilter::Linear, Some(ImageFilter::Linear)) => glow::LINEAR_MIPMAP_LINEAR,
+    }
+}
+
+fn gl_image_wrap(wrap: ImageWrap) -> u32 {
+    match wrap {
+        ImageWrap::Clamp => glow::CLAMP_TO_EDGE,
+        ImageWrap::Repeat => glow::REPEAT,
+        ImageWrap::MirroredRepeat => glow::MIRRORED_REPEAT,
+        ImageWrap::ClampToBorder => glow::CLAMP_TO_BORDER,
+    }
+}
+
+/// Returns the internal format, format and type of an image format.
+fn gl_image_format(format: ImageFormat) -> (u32, u32, u32) {
+    match format {
+        ImageFormat::R8 => (glow::R8, glow::RED, glow::UNSIGNED_BYTE),
+        ImageFormat::Rg8 => (glow::RG8, glow::RG, glow::UNSIGNED_BYTE),
+        ImageFormat::Rgb8 => (glow::RGB8, glow::RGB, glow::UNSIGNED_BYTE),
+        ImageFormat::Rgba8 => (glow::RGBA8, glow::RGBA, glow::UNSIGNED_BYTE),
+        ImageFormat::Srgb8 => (glow::SRGB8, glow::RGB, glow::UNSIGNED_BYTE),
+        ImageFormat::Srgba8 => (glow::SRGB8_ALPHA8, glow::RGBA, glow::UNSIGNED_BYTE),
+        ImageFormat::R16F => (glow::R16F, glow::RED, glow::HALF_FLOAT),
+        ImageFormat::Rgba16F => (glow::RGBA16F, glow::RGBA, glow::HALF_FLOAT),
+        ImageFormat::R32F => (glow::R32F, glow::RED, glow::FLOAT),
+        ImageFormat::Rgba32F => (glow::RGBA32F, glow::RGBA, glow::FLOAT),
+        ImageFormat::R8Ui => (glow::R8UI, glow::RED_INTEGER, glow::UNSIGNED_BYTE),
+        ImageFormat::Rgba8Ui => (glow::RGBA8UI, glow::RGBA_INTEGER, glow::UNSIGNED_BYTE),
+        ImageFormat::R16Ui => (glow::R16UI, glow::RED_INTEGER, glow::UNSIGNED_SHORT),
+        ImageFormat::R32Ui => (glow::R32UI, glow::RED_INTEGER, glow::UNSIGNED_INT),
+        ImageFormat::R32I => (glow::R32I, glow::RED_INTEGER, glow::INT),
+        ImageFormat::Rgba32Ui => (glow::RGBA32UI, glow::RGBA_INTEGER, glow::UNSIGNED_INT),
+        ImageFormat::Depth16 => (
+            glow::DEPTH_COMPONENT16,
+            glow::DEPTH_COMPONENT,
+            glow::UNSIGNED_SHORT,
+        ),
+        ImageFormat::Depth24 => (
+            glow::DEPTH_COMPONENT24,
+            glow::DEPTH_COMPONENT,
+            glow::UNSIGNED_INT,
+        ),
+        ImageFormat::Depth32F => (glow::DEPTH_COMPONENT32F, glow::DEPTH_COMPONENT, glow::FLOAT),
+        ImageFormat::Depth24Stencil8 => (
+            glow::DEPTH24_STENCIL8,
+            glow::DEPTH_STENCIL,
+            glow::UNSIGNED_INT_24_8,
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cached_texture_still_selects_its_unit() {
+        let mut active = None;
+        let mut units = Vec::new();
+
+        // Bind texture 1 to unit 0 for an upload, then texture 2 to unit 1
+        // for a draw.
+        assert_eq!(select_texture(&mut active, &mut units, 0, 1), (true, true));
+        assert_eq!(select_texture(&mut active, &mut units, 1, 2), (true, true));
+
+        // Uploading to texture 1 again needs no rebind, but must still make
+        // unit 0 active, or the upload would go to texture 2.
+        assert_eq!(select_texture(&mut active, &mut units, 0, 1), (true, false));
+        assert_eq!(active, Some(0));
+        assert_eq!(
+            select_texture(&mut active, &mut units, 0, 1),
+            (false, false)
+        );
+    }
+
+    fn diagnostic(line: Option<u32>, column: Option<u32>, message: &str) -> ShaderDiagnostic {
+        ShaderDiagnostic {
+            line,
+            column,
+            message: message.into(),
+        }
+    }
+
+    #[test]
+    fn parses_mesa_log() {
+        assert_eq!(
+            parse_info_log("0:12(5): error: `foo' undeclared\n"),
+            [diagnostic(Some(12), Some(5), "error: `foo' undeclared")]
+        );
+    }
+
+    #[test]
+    fn parses_nvidia_log() {
+        assert_eq!(
+            parse_info_log("0(12) : error C0000: syntax error, unexpected '}'"),
+            [diagnostic(
+                Some(12),
+                None,
+                "error C0000: syntax error, unexpected '}'"
+            )]
+        );
+    }
+
+    #[test]
+    fn parses_angle_log() {
+        assert_eq!(
+            parse_info_log("ERROR: 0:12: 'foo' : undeclared identifier"),
+            [diagnostic(
+                Some(12),
+                None,
+                "error: 'foo' : undeclared identifier"
+            )]
+        );
+        assert_eq!(
+            parse_info_log("WARNING: 0:3: extension not supported"),
+            [diagnostic(
+                Some(3),
+                None,
+                "warning: extension not supported"
+            )]
+        );
+    }
+
+    #[test]
+    fn parses_multi_line_log() {
+        let log = "0:3(10): warning: unused variable\n\n  0:7(1): error: syntax error\r\nERROR: 2 compilation errors.  No code generated.\n";
+        assert_eq!(
+            parse_info_log(log),
+            [
+                diagnostic(Some(3), Some(10), "warning: unused variable"),
+                diagnostic(Some(7), Some(1), "error: syntax error"),
+                diagnostic(
+                    None,
+                    None,
+                    "ERROR: 2 compilation errors.  No code generated."
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_unrecognized_lines_verbatim() {
+        assert_eq!(
+            parse_info_log(
+                "Link failed because of missing vertex shader.\n0:12(x): error: bad column"
+            ),
+            [
+                diagnostic(None, None, "Link failed because of missing vertex shader."),
+                diagnostic(None, None, "0:12(x): error: bad column"),
+            ]
+        );
+        assert_eq!(parse_info_log(""), []);
+    }
+}
@@ -0,0 +1,210 @@
+//! Rendering backends.
+//!
+//! The public resource types and [`Context`](crate::Context) perform all
+//! validation and bookkeeping, and forward the actual work to a [`Backend`]
+//! through plain resource ids. Backends own the native objects behind those
+//! ids.
+
+use alloc::vec::Vec;
+
+use crate::{
+    BufferKind, BufferUsage, Error, ImageFormat, ImageKind, ImageUniform, IndexType, PassAction,
+    Pipeline, PipelineInternal, PrimitiveType, SamplerState, Uniform, UniformBlockBinding,
+    VertexAttributeInternal,
+};
+
+mod gl;
+#[cfg(feature = "recording")]
+mod recording;
+#[cfg(feature = "software")]
+mod software;
+
+pub(crate) use gl::GlBackend;
+#[cfg(feature = "recording")]
+pub use recording::Command;
+#[cfg(feature = "recording")]
+pub(crate) use recording::RecordingBackend;
+#[cfg(feature = "software")]
+pub(crate) use software::SoftwareBackend;
+#[cfg(feature = "software")]
+pub use software::{FragmentInput, SoftwareShader, VertexInput, VertexOutput};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct BufferId(pub u32);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct ImageId(pub u32);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct SamplerId(pub u32);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct ShaderId(pub u32);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct PassId(pub u32);
+
+/// Everything a backend needs to know to create an image.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct ImageDesc {
+    pub kind: ImageKind,
+    pub width: u32,
+    pub height: u32,
+    pub format: ImageFormat,
+    pub mip_count: u32,
+}
+
+impl ImageDesc {
+    /// Returns the width, height and number of layers of a mip level.
+    pub fn level_size(&self, level: u32) -> (u32, u32, u32) {
+        let layers = match self.kind {
+            ImageKind::D2 => 1,
+            ImageKind::Cube => 6,
+            ImageKind::Array { layers } => layers,
+            ImageKind::D3 { depth } => (depth >> level).max(1),
+        };
+        (
+            (self.width >> level).max(1),
+            (self.height >> level).max(1),
+            layers,
+        )
+    }
+
+    /// Returns the size in bytes of every layer of a mip level.
+    pub fn level_len(&self, level: u32) -> usize {
+        let (width, height, layers) = self.level_size(level);
+        width as usize * height as usize * layers as usize * self.format.size()
+    }
+}
+
+/// The interface between the public API and a rendering backend.
+///
+/// Arguments are validated by the caller; backends may assume ids are live
+/// and sizes are in bounds.
+pub(crate) trait Backend {
+    fn create_buffer(
+        &self,
+        kind: BufferKind,
+        usage: BufferUsage,
+        size: usize,
+        data: Option<&[u8]>,
+    ) -> Result<BufferId, Error>;
+    fn update_buffer(&self, buffer: BufferId, offset: usize, data: &[u8]);
+    /// Discards the contents of a buffer, leaving them undefined.
+    fn invalidate_buffer(&self, buffer: BufferId);
+    fn destroy_buffer(&self, buffer: BufferId);
+
+    fn create_image(&self, desc: &ImageDesc, data: Option<&[u8]>) -> Result<ImageId, Error>;
+    #[allow(clippy::too_many_arguments)]
+    fn update_image(
+        &self,
+        image: ImageId,
+        level: u32,
+        layer: u32,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+        data: &[u8],
+    );
+    fn generate_mipmaps(&self, image: ImageId);
+    /// Reads a part of the first layer of the first mip level of an image.
+    fn read_image(&self, image: ImageId, x: u32, y: u32, width: u32, height: u32, buf: &mut [u8]);
+    fn destroy_image(&self, image: ImageId);
+
+    fn create_sampler(&self, state: &SamplerState) -> Result<SamplerId, Error>;
+    fn destroy_sampler(&self, sampler: SamplerId);
+
+    fn create_shader(
+        &self,
+        vert_source: &[u8],
+        frag_source: &[u8],
+        uniforms: &[Uniform],
+        images: &[ImageUniform],
+        uniform_blocks: &[UniformBlockBinding],
+    ) -> Result<ShaderId, Error>;
+    fn attrib_location(&self, shader: ShaderId, name: &str) -> Option<u32>;
+    fn destroy_shader(&self, shader: ShaderId);
+
+    fn create_pass(
+        &self,
+        color: &[ImageId],
+        depth_stencil: Option<ImageId>,
+        width: u32,
+        height: u32,
+    ) -> Result<PassId, Error>;
+    fn destroy_pass(&self, pass: PassId);
+
+    /// Begins a render pass, or the default pass if `pass` is `None`.
+    fn begin_pass(&self, pass: Option<PassId>, action: PassAction);
+    fn end_pass(&self);
+    fn set_viewport(&self, x: i32, y: i32, width: i32, height: i32);
+    /// Reads pixels of the current render target in [`ImageFormat::Rgba8`].
+    fn read_pixels(&self, x: i32, y: i32, width: i32, height: i32, buf: &mut [u8]);
+
+    fn apply_pipeline(&self, handle: Pipeline, pipeline: &PipelineInternal);
+    /// Binds `(buffer, offset)` pairs, where `offset` is added to the offsets
+    /// of the attributes sourced from the buffer.
+    fn apply_vertex_buffers(
+        &self,
+        attrs: &[Vec<VertexAttributeInternal>],
+        buffers: &[(BufferId, usize)],
+    );
+    fn apply_index_buffer(&self, buffer: BufferId);
+    /// Uploads the uniforms of `shader`. `data` is aligned to 4 bytes and
+    /// laid out as described in [`Uniform`].
+    fn apply_uniforms(&self, shader: ShaderId, data: &[u8]);
+    fn apply_uniform_buffer(&self, binding: u32, buffer: BufferId, offset: usize, size: usize);
+    fn apply_images(&self, shader: ShaderId, images: &[(ImageId, SamplerId)]);
+    /// Draws with the current bindings, using the index buffer if
+    /// `index_type` is `Some`.
+    fn draw(
+        &self,
+        primitive: PrimitiveType,
+        index_type: Option<IndexType>,
+        start: usize,
+        count: usize,
+        instances: usize,
+    );
+    fn commit_frame(&self);
+
+    fn uniform_buffer_offset_alignment(&self) -> usize;
+}
+
+/// A simple slot allocator mapping resource ids to backend objects.
+pub(crate) struct Slots<T> {
+    items: Vec<Option<T>>,
+    free: Vec<u32>,
+}
+
+impl<T> Slots<T> {
+    pub fn new() -> Self {
+        Self {
+            items: Vec::new(),
+            free: Vec::new(),
+        }
+    }
+
+    pub fn insert(&mut self, item: T) -> u32 {
+        match self.free.pop() {
+            Some(id) => {
+                self.items[id as usize] = Some(item);
+                id
+            }
+            None => {
+                self.items.push(Some(item));
+                (self.items.len() - 1) as u32
+            }
+        }
+    }
+
+    pub fn get(&self, id: u32) -> &T {
+        self.items[id as usize].as_ref().unwrap()
+    }
+
+    pub fn remove(&mut self, id: u32) -> T {
+        let item = self.items[id as usize].take().unwrap();
+        self.free.push(id);
+        item
+    }
+}
@@ -0,0 +1,518 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::cell::{Cell, RefCell};
+
+use super::{Backend, BufferId, ImageDesc, ImageId, PassId, SamplerId, ShaderId};
+use crate::{
+    BufferKind, BufferUsage, Error, ImageFormat, ImageKind, ImageUniform, IndexType, PassAction,
+    Pipeline, PipelineInternal, PrimitiveType, SamplerState, Uniform, UniformBlockBinding,
+    VertexAttributeInternal,
+};
+
+/// A command recorded by a context created with
+/// [`Context::new_recording`](crate::Context::new_recording).
+///
+/// Resources are referred to by the ids returned by their `id` methods, e.g.
+/// [`Buffer::id`](crate::Buffer::id). Ids are never reused.
+#[allow(missing_docs)]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    CreateBuffer {
+        buffer: u32,
+        kind: BufferKind,
+        usage: BufferUsage,
+        size: usize,
+    },
+    UpdateBuffer {
+        buffer: u32,
+        offset: usize,
+        data: Vec<u8>,
+    },
+    InvalidateBuffer {
+        buffer: u32,
+    },
+    DestroyBuffer {
+        buffer: u32,
+    },
+    CreateImage {
+        image: u32,
+        kind: ImageKind,
+        width: u32,
+        height: u32,
+        format: ImageFormat,
+        mip_count: u32,
+    },
+    UpdateImage {
+        image: u32,
+        level: u32,
+        layer: u32,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    },
+    GenerateMipmaps {
+        image: u32,
+    },
+    /// Reads back a part of an image. The buffer is filled with zeros.
+    ReadImage {
+        image: u32,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    },
+    DestroyImage {
+        image: u32,
+    },
+    CreateSampler {
+        sampler: u32,
+        state: SamplerState,
+    },
+    DestroySampler {
+        sampler: u32,
+    },
+    CreateShader {
+        shader: u32,
+    },
+    DestroyShader {
+        shader: u32,
+    },
+    CreatePass {
+        pass: u32,
+        color: Vec<u32>,
+        depth_stencil: Option<u32>,
+    },
+    DestroyPass {
+        pass: u32,
+    },
+    /// Begins a render pass, or the default pass if `pass` is `None`.
+    BeginPass {
+        pass: Option<u32>,
+        action: PassAction,
+    },
+    EndPass,
+    SetViewport {
+        x: i32,
+        y: i32,
+        width: i32,
+        height: i32,
+    },
+    /// Reads back pixels of the current render target. The buffer is filled
+    /// with zeros.
+    ReadPixels {
+        x: i32,
+        y: i32,
+        width: i32,
+        height: i32,
+    },
+    SetPipeline {
+        pipeline: Pipeline,
+        shader: u32,
+    },
+    /// Binds `(buffer, offset)` pairs to vertex buffer slots in order.
+    SetVertexBuffers {
+        buffers: Vec<(u32, usize)>,
+    },
+    SetIndexBuffer {
+        buffer: u32,
+    },
+    SetUniforms {
+        shader: u32,
+        data: Vec<u8>,
+    },
+    SetUniformBuffer {
+        binding: u32,
+        buffer: u32,
+        offset: usize,
+        size: usize,
+    },
+    /// Binds `(image, sampler)` pairs to texture units in order.
+    SetImages {
+        images: Vec<(u32, u32)>,
+    },
+    /// A draw call along with the state bound at the time.
+    Draw {
+        primitive: PrimitiveType,
+        /// The index type, or `None` for non-indexed draws.
+        index_type: Option<IndexType>,
+        start: usize,
+        count: usize,
+        instances: usize,
+        pipeline: Pipeline,
+        /// The `(buffer, offset)` pairs bound to each vertex buffer slot.
+        vertex_buffers: Vec<(u32, usize)>,
+        index_buffer: Option<u32>,
+        /// The `(image, sampler)` pairs bound to each texture unit.
+        images: Vec<(u32, u32)>,
+    },
+    CommitFrame,
+}
+
+#[derive(Default)]
+struct BoundState {
+    pipeline: Option<Pipeline>,
+    vertex_buffers: Vec<(u32, usize)>,
+    index_buffer: Option<u32>,
+    images: Vec<(u32, u32)>,
+}
+
+/// A backend that records every call instead of rendering.
+pub(crate) struct RecordingBackend {
+    commands: RefCell<Vec<Command>>,
+    next_id: Cell<u32>,
+    live: RefCell<Vec<u32>>,
+    attrib_names: RefCell<Vec<(u32, Vec<String>)>>,
+    bound: RefCell<BoundState>,
+}
+
+impl RecordingBackend {
+    pub fn new() -> Self {
+        Self {
+            commands: RefCell::new(Vec::new()),
+            next_id: Cell::new(0),
+            live: RefCell::new(Vec::new()),
+            attrib_names: RefCell::new(Vec::new()),
+            bound: RefCell::new(BoundState::default()),
+        }
+    }
+
+    pub fn commands(&self) -> Vec<Command> {
+        self.commands.borrow().clone()
+    }
+
+    pub fn clear_commands(&self) {
+        self.commands.borrow_mut().clear();
+    }
+
+    pub fn live_resources(&self) -> usize {
+        self.live.borrow().len()
+    }
+
+    fn record(&self, command: Command) {
+        self.commands.borrow_mut().push(command);
+    }
+
+    fn create(&self) -> u32 {
+        let id = self.next_id.get();
+        self.next_id.set(id + 1);
+        self.live.borrow_mut().push(id);
+        id
+    }
+
+    fn check(&self, id: u32) -> u32 {
+        assert!(
+            self.live.borrow().contains(&id),
+            "Resource {} used after being destroyed",
+            id
+        );
+        id
+    }
+
+    fn destroy(&self, id: u32) -> u32 {
+        let mut live = self.live.borrow_mut();
+        let index = live
+            .iter()
+            .position(|&live_id| live_id == id)
+            .unwrap_or_else(|| panic!("Resource {} destroyed twice", id));
+        live.swap_remove(index);
+        id
+    }
+}
+
+impl Backend for RecordingBackend {
+    fn create_buffer(
+        &self,
+        kind: BufferKind,
+        usage: BufferUsage,
+        size: usize,
+        data: Option<&[u8]>,
+    ) -> Result<BufferId, Error> {
+        let buffer = self.create();
+        self.record(Command::CreateBuffer {
+            buffer,
+            kind,
+            usage,
+            size,
+        });
+        if let Some(data) = data {
+            self.record(Command::UpdateBuffer {
+                buffer,
+                offset: 0,
+                data: data.into(),
+            });
+        }
+        Ok(BufferId(buffer))
+    }
+
+    fn update_buffer(&self, buffer: BufferId, offset: usize, data: &[u8]) {
+        self.record(Command::UpdateBuffer {
+            buffer: self.check(buffer.0),
+            offset,
+            data: data.into(),
+        });
+    }
+
+    fn invalidate_buffer(&self, buffer: BufferId) {
+        self.record(Command::InvalidateBuffer {
+            buffer: self.check(buffer.0),
+        });
+    }
+
+    fn destroy_buffer(&self, buffer: BufferId) {
+        self.record(Command::DestroyBuffer {
+            buffer: self.destroy(buffer.0),
+        });
+    }
+
+    fn create_image(&self, desc: &ImageDesc, _data: Option<&[u8]>) -> Result<ImageId, Error> {
+        let image = self.create();
+        self.record(Command::CreateImage {
+            image,
+            kind: desc.kind,
+            width: desc.width,
+            height: desc.height,
+            format: desc.format,
+            mip_count: desc.mip_count,
+        });
+        Ok(ImageId(image))
+    }
+
+    fn update_image(
+        &self,
+        image: ImageId,
+        level: u32,
+        layer: u32,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+        _data: &[u8],
+    ) {
+        self.record(Command::UpdateImage {
+            image: self.check(image.0),
+            level,
+            layer,
+            x,
+            y,
+            width,
+            height,
+        });
+    }
+
+    fn generate_mipmaps(&self, image: ImageId) {
+        self.record(Command::GenerateMipmaps {
+            image: self.check(image.0),
+        });
+    }
+
+    fn read_image(&self, image: ImageId, x: u32, y: u32, width: u32, height: u32, buf: &mut [u8]) {
+        buf.fill(0);
+        self.record(Command::ReadImage {
+            image: self.check(image.0),
+            x,
+            y,
+            width,
+            height,
+        });
+    }
+
+    fn destroy_image(&self, image: ImageId) {
+        self.record(Command::DestroyImage {
+            image: self.destroy(image.0),
+        });
+    }
+
+    fn create_sampler(&self, state: &SamplerState) -> Result<SamplerId, Error> {
+        let sampler = self.create();
+        self.record(Command::CreateSampler {
+            sampler,
+            state: *state,
+        });
+        Ok(SamplerId(sampler))
+    }
+
+    fn destroy_sampler(&self, sampler: SamplerId) {
+        self.record(Command::DestroySampler {
+            sampler: self.destroy(sampler.0),
+        });
+    }
+
+    fn create_shader(
+        &self,
+        _vert_source: &[u8],
+        _frag_source: &[u8],
+        _uniforms: &[Uniform],
+        _images: &[ImageUniform],
+        _uniform_blocks: &[UniformBlockBinding],
+    ) -> Result<ShaderId, Error> {
+        let shader = self.create();
+        self.attrib_names.borrow_mut().push((shader, Vec::new()));
+        self.record(Command::CreateShader { shader });
+        Ok(ShaderId(shader))
+    }
+
+    fn attrib_location(&self, shader: ShaderId, name: &str) -> Option<u32> {
+        // Any attribute exists; locations are assigned in order of first use.
+        let shader = self.check(shader.0);
+        let mut attrib_names = self.attrib_names.borrow_mut();
+        let (_, names) = attrib_names
+            .iter_mut()
+            .find(|(id, _)| *id == shader)
+            .unwrap();
+        let location = match names.iter().position(|n| n == name) {
+            Some(location) => location,
+            None => {
+                names.push(name.into());
+                names.len() - 1
+            }
+        };
+        Some(location as u32)
+    }
+
+    fn destroy_shader(&self, shader: ShaderId) {
+        self.attrib_names
+            .borrow_mut()
+            .retain(|(id, _)| *id != shader.0);
+        self.record(Command::DestroyShader {
+            shader: self.destroy(shader.0),
+        });
+    }
+
+    fn create_pass(
+        &self,
+        color: &[ImageId],
+        depth_stencil: Option<ImageId>,
+        _width: u32,
+        _height: u32,
+    ) -> Result<PassId, Error> {
+        let pass = self.create();
+        self.record(Command::CreatePass {
+            pass,
+            color: color.iter().map(|image| self.check(image.0)).collect(),
+            depth_stencil: depth_stencil.map(|image| self.check(image.0)),
+        });
+        Ok(PassId(pass))
+    }
+
+    fn destroy_pass(&self, pass: PassId) {
+        self.record(Command::DestroyPass {
+            pass: self.destroy(pass.0),
+        });
+    }
+
+    fn begin_pass(&self, pass: Option<PassId>, action: PassAction) {
+        self.record(Command::BeginPass {
+            pass: pass.map(|pass| self.check(pass.0)),
+            action,
+        });
+    }
+
+    fn end_pass(&self) {
+        self.record(Command::EndPass);
+    }
+
+    fn set_viewport(&self, x: i32, y: i32, width: i32, height: i32) {
+        self.record(Command::SetViewport {
+            x,
+            y,
+            width,
+            height,
+        });
+    }
+
+    fn read_pixels(&self, x: i32, y: i32, width: i32, height: i32, buf: &mut [u8]) {
+        buf.fill(0);
+        self.record(Command::ReadPixels {
+            x,
+            y,
+            width,
+            height,
+        });
+    }
+
+    fn apply_pipeline(&self, handle: Pipeline, pipeline: &PipelineInternal) {
+        self.bound.borrow_mut().pipeline = Some(handle);
+        self.record(Command::SetPipeline {
+            pipeline: handle,
+            shader: self.check(pipeline.shader.internal.id.0),
+        });
+    }
+
+    fn apply_vertex_buffers(
+        &self,
+        _attrs: &[Vec<VertexAttributeInternal>],
+        buffers: &[(BufferId, usize)],
+    ) {
+        let buffers = buffers
+            .iter()
+            .map(|(buffer, offset)| (self.check(buffer.0), *offset))
+            .collect::<Vec<_>>();
+        self.bound.borrow_mut().vertex_buffers = buffers.clone();
+        self.record(Command::SetVertexBuffers { buffers });
+    }
+
+    fn apply_index_buffer(&self, buffer: BufferId) {
+        let buffer = self.check(buffer.0);
+        self.bound.borrow_mut().index_buffer = Some(buffer);
+        self.record(Command::SetIndexBuffer { buffer });
+    }
+
+    fn apply_uniforms(&self, shader: ShaderId, data: &[u8]) {
+        self.record(Command::SetUniforms {
+            shader: self.check(shader.0),
+            data: data.into(),
+        });
+    }
+
+    fn apply_uniform_buffer(&self, binding: u32, buffer: BufferId, offset: usize, size: usize) {
+        self.record(Command::SetUniformBuffer {
+            binding,
+            buffer: self.check(buffer.0),
+            offset,
+            size,
+        });
+    }
+
+    fn apply_images(&self, _shader: ShaderId, images: &[(ImageId, SamplerId)]) {
+        let images = images
+            .iter()
+            .map(|(image, sampler)| (self.check(image.0), self.check(sampler.0)))
+            .collect::<Vec<_>>();
+        self.bound.borrow_mut().images = images.clone();
+        self.record(Command::SetImages { images });
+    }
+
+    fn draw(
+        &self,
+        primitive: PrimitiveType,
+        index_type: Option<IndexType>,
+        start: usize,
+        count: usize,
+        instances: usize,
+    ) {
+        let bound = self.bound.borrow();
+        self.record(Command::Draw {
+            primitive,
+            index_type,
+            start,
+            count,
+            instances,
+            pipeline: bound.pipeline.unwrap(),
+            vertex_buffers: bound.vertex_buffers.clone(),
+            index_buffer: index_type.and(bound.index_buffer),
+            images: bound.images.clone(),
+        });
+    }
+
+    fn commit_frame(&self) {
+        *self.bound.borrow_mut() = BoundState::default();
+        self.record(Command::CommitFrame);
+    }
+
+    fn uniform_buffer_offset_alignment(&self) -> usize {
+        // A common value for desktop drivers, so that misaligned offsets are
+        // caught in tests.
+        256
+    }
+}
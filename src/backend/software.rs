@@ -0,0 +1,1685 @@
+use alloc::boxed::Box;
+use alloc::rc::Rc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cell::{Cell, Ref, RefCell, RefMut};
+use core::{f32, mem, ptr};
+
+use super::{Backend, BufferId, ImageDesc, ImageId, PassId, SamplerId, ShaderId, Slots};
+use crate::{
+    BlendFactor, BlendOp, BlendState, BufferKind, BufferUsage, CompareFunc, CullMode, DepthState,
+    Error, FrontFace, ImageFilter, ImageFormat, ImageKind, ImageUniform, ImageWrap, IndexType,
+    PassAction, Pipeline, PipelineInternal, PrimitiveType, RasterState, SamplerState, StencilOp,
+    StencilState, Uniform, UniformBlock, UniformBlockBinding, VertexAttributeInternal,
+    VertexFormat,
+};
+
+/// A shader for contexts created with
+/// [`Context::new_software`](crate::Context::new_software), written as Rust
+/// closures instead of GLSL.
+#[allow(clippy::type_complexity)]
+pub struct SoftwareShader {
+    /// The names of the vertex attributes. The index of each name is its
+    /// location in [`VertexInput::attributes`].
+    pub attributes: &'static [&'static str],
+    /// The vertex stage.
+    pub vertex: Box<dyn Fn(&VertexInput) -> VertexOutput>,
+    /// The fragment stage, returning the color written to the first color
+    /// attachment, or `None` to discard the fragment.
+    pub fragment: Box<dyn Fn(&FragmentInput) -> Option<[f32; 4]>>,
+}
+
+/// The input of a [`SoftwareShader`] vertex stage.
+pub struct VertexInput<'a> {
+    /// The vertex attributes, indexed by location. Components missing from the
+    /// attribute's [`VertexFormat`] are filled in from `(0, 0, 0, 1)`.
+    pub attributes: &'a [[f32; 4]],
+    /// The index of the vertex.
+    pub vertex_index: usize,
+    /// The index of the instance.
+    pub instance_index: usize,
+    uniforms: &'a [u8],
+}
+
+impl VertexInput<'_> {
+    /// Returns the uniforms set with
+    /// [`Context::set_uniforms`](crate::Context::set_uniforms).
+    ///
+    /// # Panics
+    /// Panics if the size of `T` does not match the size of the uniforms.
+    pub fn uniforms<T: UniformBlock + Copy>(&self) -> T {
+        read_uniforms(self.uniforms)
+    }
+}
+
+/// The output of a [`SoftwareShader`] vertex stage.
+#[derive(Debug, Clone, Default)]
+pub struct VertexOutput {
+    /// The clip space position.
+    pub position: [f32; 4],
+    /// Values interpolated across the primitive and passed to the fragment
+    /// stage.
+    pub varyings: Vec<f32>,
+}
+
+/// The input of a [`SoftwareShader`] fragment stage.
+pub struct FragmentInput<'a> {
+    /// The window space position of the fragment, with the pixel center in
+    /// `x` and `y`, the depth in `z` and `1 / w` in `w`.
+    pub position: [f32; 4],
+    /// Whether the primitive is front-facing.
+    pub front_facing: bool,
+    /// The interpolated varyings of the vertex stage.
+    pub varyings: &'a [f32],
+    uniforms: &'a [u8],
+    images: &'a [Texture<'a>],
+}
+
+impl FragmentInput<'_> {
+    /// Returns the uniforms set with
+    /// [`Context::set_uniforms`](crate::Context::set_uniforms).
+    ///
+    /// # Panics
+    /// Panics if the size of `T` does not match the size of the uniforms.
+    pub fn uniforms<T: UniformBlock + Copy>(&self) -> T {
+        read_uniforms(self.uniforms)
+    }
+
+    /// Samples the image bound to the given texture unit with its sampler.
+    /// Texture coordinate `(0, 0)` is the first pixel of the image data.
+    ///
+    /// Returns `(0, 0, 0, 1)` if no image is bound to the unit. Depth images
+    /// return their depth in the first component.
+    pub fn sample(&self, image: usize, coord: [f32; 2]) -> [f32; 4] {
+        self.sample_lod(image, coord, 0.0)
+    }
+
+    /// Samples the image bound to the given texture unit at an explicit level
+    /// of detail, like `textureLod`. The sampler's LOD bias and clamp still
+    /// apply.
+    ///
+    /// Software shaders have no derivatives, so [`sample`](Self::sample)
+    /// always uses a level of detail of 0, i.e. the magnification filter;
+    /// minification and mipmapping need an explicit `lod`.
+    pub fn sample_lod(&self, image: usize, [u, v]: [f32; 2], lod: f32) -> [f32; 4] {
+        self.sample_texture(image, [u, v, 0.0], lod)
+    }
+
+    /// Samples the array image bound to the given texture unit at an explicit
+    /// level of detail, like `textureLod` with a `sampler2DArray`. The third
+    /// coordinate is the layer, rounded to the nearest one.
+    pub fn sample_array(&self, image: usize, [u, v, layer]: [f32; 3], lod: f32) -> [f32; 4] {
+        self.sample_texture(image, [u, v, floor(layer + 0.5) as f32], lod)
+    }
+
+    /// Samples the cube map bound to the given texture unit in a direction at
+    /// an explicit level of detail, like `textureLod` with a `samplerCube`.
+    pub fn sample_cube(&self, image: usize, direction: [f32; 3], lod: f32) -> [f32; 4] {
+        self.sample_texture(image, cube_coord(direction), lod)
+    }
+
+    /// Samples the 3D image bound to the given texture unit at an explicit
+    /// level of detail, like `textureLod` with a `sampler3D`.
+    pub fn sample_3d(&self, image: usize, coord: [f32; 3], lod: f32) -> [f32; 4] {
+        self.sample_texture(image, coord, lod)
+    }
+
+    fn sample_texture(&self, image: usize, coord: [f32; 3], lod: f32) -> [f32; 4] {
+        self.images
+            .get(image)
+            .map_or([0.0, 0.0, 0.0, 1.0], |texture| texture.sample(coord, lod))
+    }
+
+    /// Samples the depth image bound to the given texture unit, comparing
+    /// `reference` against the stored depth with the function set by
+    /// [`SamplerState::compare`](crate::SamplerState::compare), like a
+    /// `sampler2DShadow`.
+    ///
+    /// Returns `0.0` if no image is bound to the unit or the sampler has no
+    /// comparison function.
+    pub fn sample_compare(&self, image: usize, [u, v]: [f32; 2], reference: f32) -> f32 {
+        self.images.get(image).map_or(0.0, |texture| {
+            texture.sample_compare([u, v, 0.0], reference)
+        })
+    }
+}
+
+fn read_uniforms<T: UniformBlock + Copy>(data: &[u8]) -> T {
+    assert_eq!(
+        data.len(),
+        mem::size_of::<T>(),
+        "Uniform data size does not match the shader's uniforms"
+    );
+    // SAFETY: `UniformBlock` types consist only of `f32`s and `i32`s, for
+    // which any bit pattern is valid.
+    unsafe { ptr::read_unaligned(data.as_ptr() as *const T) }
+}
+
+struct SwBuffer {
+    data: RefCell<Vec<u8>>,
+}
+
+struct SwImage {
+    desc: ImageDesc,
+    data: Rc<RefCell<Vec<u8>>>,
+}
+
+struct SwShader {
+    shader: SoftwareShader,
+    uniforms: RefCell<Vec<u8>>,
+}
+
+/// A render pass, sharing the storage of its attachments so that it keeps
+/// rendering into them even after their images are destroyed.
+struct SwPass {
+    color: Option<(ImageFormat, Rc<RefCell<Vec<u8>>>)>,
+    depth_stencil: Option<(ImageFormat, Rc<RefCell<Vec<u8>>>)>,
+    width: u32,
+    height: u32,
+}
+
+struct SwPipeline {
+    shader: ShaderId,
+    depth: DepthState,
+    stencil: Option<StencilState>,
+    raster: RasterState,
+    blend: Option<BlendState>,
+    alpha_blend: Option<BlendState>,
+    blend_color: [f32; 4],
+}
+
+#[derive(Default)]
+struct BoundState {
+    pass: Option<PassId>,
+    pipeline: Option<SwPipeline>,
+    attrs: Vec<Vec<VertexAttributeInternal>>,
+    vertex_buffers: Vec<(BufferId, usize)>,
+    index_buffer: Option<BufferId>,
+    images: Vec<(ImageId, SamplerId)>,
+}
+
+/// A backend that rasterizes on the CPU.
+///
+/// Rendering is deterministic: the same calls always produce the same pixels.
+/// Shaders are [`SoftwareShader`]s. Uniform buffers and color attachments
+/// other than the first are ignored. The default framebuffer has
+/// an [`ImageFormat::Rgba8`] color buffer and an
+/// [`ImageFormat::Depth24Stencil8`] depth/stencil buffer.
+pub(crate) struct SoftwareBackend {
+    width: u32,
+    height: u32,
+    color: RefCell<Vec<u8>>,
+    depth_stencil: RefCell<Vec<u8>>,
+    buffers: RefCell<Slots<SwBuffer>>,
+    images: RefCell<Slots<SwImage>>,
+    samplers: RefCell<Slots<SamplerState>>,
+    shaders: RefCell<Slots<SwShader>>,
+    passes: RefCell<Slots<Rc<SwPass>>>,
+    viewport: Cell<[i32; 4]>,
+    saved_viewport: Cell<Option<[i32; 4]>>,
+    bound: RefCell<BoundState>,
+}
+
+impl SoftwareBackend {
+    pub fn new(width: u32, height: u32) -> Self {
+        let pixels = width as usize * height as usize;
+        let mut depth_stencil = vec![0; pixels * ImageFormat::Depth24Stencil8.size()];
+        for pixel in depth_stencil.chunks_exact_mut(ImageFormat::Depth24Stencil8.size()) {
+            write_color(ImageFormat::Depth24Stencil8, [1.0; 4], pixel);
+        }
+        Self {
+            width,
+            height,
+            color: RefCell::new(vec![0; pixels * 4]),
+            depth_stencil: RefCell::new(depth_stencil),
+            buffers: RefCell::new(Slots::new()),
+            images: RefCell::new(Slots::new()),
+            samplers: RefCell::new(Slots::new()),
+            shaders: RefCell::new(Slots::new()),
+            passes: RefCell::new(Slots::new()),
+            viewport: Cell::new([0, 0, width as i32, height as i32]),
+            saved_viewport: Cell::new(None),
+            bound: RefCell::new(BoundState::default()),
+        }
+    }
+
+    pub fn create_software_shader(&self, shader: SoftwareShader) -> ShaderId {
+        ShaderId(self.shaders.borrow_mut().insert(SwShader {
+            shader,
+            uniforms: RefCell::new(Vec::new()),
+        }))
+    }
+
+    fn target_size(&self, pass: Option<PassId>) -> (u32, u32) {
+        match self.pass(pass) {
+            Some(pass) => (pass.width, pass.height),
+            None => (self.width, self.height),
+        }
+    }
+
+    fn pass(&self, pass: Option<PassId>) -> Option<Rc<SwPass>> {
+        pass.map(|pass| self.passes.borrow().get(pass.0).clone())
+    }
+}
+
+impl Backend for SoftwareBackend {
+    fn create_buffer(
+        &self,
+        _kind: BufferKind,
+        _usage: BufferUsage,
+        size: usize,
+        data: Option<&[u8]>,
+    ) -> Result<BufferId, Error> {
+        let data = match data {
+            Some(data) => data.to_vec(),
+            None => vec![0; size],
+        };
+        Ok(BufferId(self.buffers.borrow_mut().insert(SwBuffer {
+            data: RefCell::new(data),
+        })))
+    }
+
+    fn update_buffer(&self, buffer: BufferId, offset: usize, data: &[u8]) {
+        let buffers = self.buffers.borrow();
+        let mut buffer = buffers.get(buffer.0).data.borrow_mut();
+        buffer[offset..offset + data.len()].copy_from_slice(data);
+    }
+
+    fn invalidate_buffer(&self, _buffer: BufferId) {
+        // Nothing to do, as there is no GPU to synchronize with.
+    }
+
+    fn destroy_buffer(&self, buffer: BufferId) {
+        self.buffers.borrow_mut().remove(buffer.0);
+    }
+
+    fn create_image(&self, desc: &ImageDesc, data: Option<&[u8]>) -> Result<ImageId, Error> {
+        // All mip levels are stored back to back, starting with level 0, and
+        // each level stores its layers back to back.
+        let mut pixels = vec![0; level_offset(desc, desc.mip_count)];
+        if let Some(data) = data {
+            pixels[..data.len()].copy_from_slice(data);
+        }
+        Ok(ImageId(self.images.borrow_mut().insert(SwImage {
+            desc: *desc,
+            data: Rc::new(RefCell::new(pixels)),
+        })))
+    }
+
+    fn update_image(
+        &self,
+        image: ImageId,
+        level: u32,
+        layer: u32,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+        data: &[u8],
+    ) {
+        let images = self.images.borrow();
+        let image = images.get(image.0);
+        let pixel_size = image.desc.format.size();
+        let row_size = width as usize * pixel_size;
+        let (level_width, level_height, _) = image.desc.level_size(level);
+        let offset = level_offset(&image.desc, level)
+            + layer as usize * level_width as usize * level_height as usize * pixel_size;
+        let mut pixels = image.data.borrow_mut();
+        for (row, src) in data
+            .chunks_exact(row_size)
+            .take(height as usize)
+            .enumerate()
+        {
+            let start =
+                offset + ((y as usize + row) * level_width as usize + x as usize) * pixel_size;
+            pixels[start..start + row_size].copy_from_slice(src);
+        }
+    }
+
+    fn generate_mipmaps(&self, image: ImageId) {
+        let images = self.images.borrow();
+        let image = images.get(image.0);
+        let desc = &image.desc;
+        let size = desc.format.size();
+        let mut pixels = image.data.borrow_mut();
+        // 3D images also average pairs of layers.
+        let depth = match desc.kind {
+            ImageKind::D3 { .. } => 2,
+            _ => 1,
+        };
+        let weight = 1.0 / (4 * depth) as f32;
+        for level in 1..desc.mip_count {
+            // Each texel is the average of the 2x2 (or 2x2x2) block it covers
+            // in the previous level, clamped at odd edges.
+            let (src_width, src_height, src_layers) = desc.level_size(level - 1);
+            let (width, height, layers) = desc.level_size(level);
+            let src_offset = level_offset(desc, level - 1);
+            let offset = level_offset(desc, level);
+            for z in 0..layers {
+                for y in 0..height {
+                    for x in 0..width {
+                        let mut color = [0.0; 4];
+                        for sz in 0..depth {
+                            for (sx, sy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                                let sx = (x * 2 + sx).min(src_width - 1) as usize;
+                                let sy = (y * 2 + sy).min(src_height - 1) as usize;
+                                let sz = (z * depth + sz).min(src_layers - 1) as usize;
+                                let start = src_offset
+                                    + ((sz * src_height as usize + sy) * src_width as usize + sx)
+                                        * size;
+                                let texel = read_color(desc.format, &pixels[start..start + size]);
+                                for (c, t) in color.iter_mut().zip(texel) {
+                                    *c += t * weight;
+                                }
+                            }
+                        }
+                        let start = offset
+                            + ((z as usize * height as usize + y as usize) * width as usize
+                                + x as usize)
+                                * size;
+                        write_color(desc.format, color, &mut pixels[start..start + size]);
+                    }
+                }
+            }
+        }
+    }
+
+    fn read_image(&self, image: ImageId, x: u32, y: u32, width: u32, height: u32, buf: &mut [u8]) {
+        let images = self.images.borrow();
+        let image = images.get(image.0);
+        let pixel_size = image.desc.format.size();
+        let row_size = width as usize * pixel_size;
+        let pixels = image.data.borrow();
+        for (row, dst) in buf
+            .chunks_exact_mut(row_size)
+            .take(height as usize)
+            .enumerate()
+        {
+            let start = ((y as usize + row) * image.desc.width as usize + x as usize) * pixel_size;
+            dst.copy_from_slice(&pixels[start..start + row_size]);
+        }
+    }
+
+    fn destroy_image(&self, image: ImageId) {
+        self.images.borrow_mut().remove(image.0);
+    }
+
+    fn create_sampler(&self, state: &SamplerState) -> Result<SamplerId, Error> {
+        Ok(SamplerId(self.samplers.borrow_mut().insert(*state)))
+    }
+
+    fn destroy_sampler(&self, sampler: SamplerId) {
+        self.samplers.borrow_mut().remove(sampler.0);
+    }
+
+    fn create_shader(
+        &self,
+        _vert_source: &[u8],
+        _frag_source: &[u8],
+        _uniforms: &[Uniform],
+        _images: &[ImageUniform],
+        _uniform_blocks: &[UniformBlockBinding],
+    ) -> Result<ShaderId, Error> {
+        // Only software shaders can be run.
+        Err(Error::Unsupported)
+    }
+
+    fn attrib_location(&self, shader: ShaderId, name: &str) -> Option<u32> {
+        let shaders = self.shaders.borrow();
+        shaders
+            .get(shader.0)
+            .shader
+            .attributes
+            .iter()
+            .position(|attr| *attr == name)
+            .map(|location| location as u32)
+    }
+
+    fn destroy_shader(&self, shader: ShaderId) {
+        self.shaders.borrow_mut().remove(shader.0);
+    }
+
+    fn create_pass(
+        &self,
+        color: &[ImageId],
+        depth_stencil: Option<ImageId>,
+        width: u32,
+        height: u32,
+    ) -> Result<PassId, Error> {
+        let images = self.images.borrow();
+        let attachment = |image: ImageId| {
+            let image = images.get(image.0);
+            (image.desc.format, image.data.clone())
+        };
+        Ok(PassId(self.passes.borrow_mut().insert(Rc::new(SwPass {
+            color: color.first().copied().map(attachment),
+            depth_stencil: depth_stencil.map(attachment),
+            width,
+            height,
+        }))))
+    }
+
+    fn destroy_pass(&self, pass: PassId) {
+        self.passes.borrow_mut().remove(pass.0);
+    }
+
+    fn begin_pass(&self, pass: Option<PassId>, action: PassAction) {
+        if pass.is_some() {
+            let (width, height) = self.target_size(pass);
+            self.saved_viewport.set(Some(self.viewport.get()));
+            self.viewport.set([0, 0, width as i32, height as i32]);
+        }
+        self.bound.borrow_mut().pass = pass;
+
+        if let PassAction::Clear {
+            color,
+            depth,
+            stencil,
+        } = action
+        {
+            let pass = self.pass(pass);
+            let mut target = Target::new(self, pass.as_deref());
+            if let Some((r, g, b, a)) = color {
+                if let Some((format, pixels)) = &mut target.color {
+                    let size = format.size();
+                    for pixel in pixels.chunks_exact_mut(size) {
+                        write_color(*format, [r, g, b, a], pixel);
+                    }
+                }
+            }
+            if let Some((format, pixels)) = &mut target.depth_stencil {
+                for pixel in pixels.chunks_exact_mut(format.size()) {
+                    if let Some(depth) = depth {
+                        write_color(*format, [depth; 4], pixel);
+                    }
+                    if let Some(stencil) = stencil {
+                        write_stencil(*format, stencil as u8, pixel);
+                    }
+                }
+            }
+        }
+    }
+
+    fn end_pass(&self) {
+        self.bound.borrow_mut().pass = None;
+        if let Some(viewport) = self.saved_viewport.take() {
+            self.viewport.set(viewport);
+        }
+    }
+
+    fn set_viewport(&self, x: i32, y: i32, width: i32, height: i32) {
+        self.viewport.set([x, y, width, height]);
+    }
+
+    fn read_pixels(&self, x: i32, y: i32, width: i32, height: i32, buf: &mut [u8]) {
+        let pass = self.pass(self.bound.borrow().pass);
+        let target = Target::new(self, pass.as_deref());
+        for (i, dst) in buf
+            .chunks_exact_mut(4)
+            .take(width as usize * height as usize)
+            .enumerate()
+        {
+            let px = x + (i % width as usize) as i32;
+            let py = y + (i / width as usize) as i32;
+            let in_bounds =
+                px >= 0 && py >= 0 && px < target.width as i32 && py < target.height as i32;
+            match &target.color {
+                Some((format, pixels)) if in_bounds => {
+                    let size = format.size();
+                    let offset = (py as usize * target.width as usize + px as usize) * size;
+                    let color = read_color(*format, &pixels[offset..offset + size]);
+                    write_color(ImageFormat::Rgba8, color, dst);
+                }
+                _ => dst.fill(0),
+            }
+        }
+    }
+
+    fn apply_pipeline(&self, _handle: Pipeline, pipeline: &PipelineInternal) {
+        let (r, g, b, a) = pipeline.blend_color;
+        self.bound.borrow_mut().pipeline = Some(SwPipeline {
+            shader: pipeline.shader.internal.id,
+            depth: pipeline.depth,
+            stencil: pipeline.stencil,
+            raster: pipeline.raster,
+            blend: pipeline.blend,
+            alpha_blend: pipeline.alpha_blend,
+            blend_color: [r, g, b, a],
+        });
+    }
+
+    fn apply_vertex_buffers(
+        &self,
+        attrs: &[Vec<VertexAttributeInternal>],
+        buffers: &[(BufferId, usize)],
+    ) {
+        let mut bound = self.bound.borrow_mut();
+        bound.attrs = attrs.to_vec();
+        bound.vertex_buffers = buffers.to_vec();
+    }
+
+    fn apply_index_buffer(&self, buffer: BufferId) {
+        self.bound.borrow_mut().index_buffer = Some(buffer);
+    }
+
+    fn apply_uniforms(&self, shader: ShaderId, data: &[u8]) {
+        let shaders = self.shaders.borrow();
+        let mut uniforms = shaders.get(shader.0).uniforms.borrow_mut();
+        uniforms.clear();
+        uniforms.extend_from_slice(data);
+    }
+
+    fn apply_uniform_buffer(&self, _binding: u32, _buffer: BufferId, _offset: usize, _size: usize) {
+        // Uniform buffers are not supported by software shaders.
+    }
+
+    fn apply_images(&self, _shader: ShaderId, images: &[(ImageId, SamplerId)]) {
+        self.bound.borrow_mut().images = images.to_vec();
+    }
+
+    fn draw(
+        &self,
+        primitive: PrimitiveType,
+        index_type: Option<IndexType>,
+        start: usize,
+        count: usize,
+        instances: usize,
+    ) {
+        let bound = self.bound.borrow();
+        let pipeline = match &bound.pipeline {
+            Some(pipeline) => pipeline,
+            None => return,
+        };
+        let shaders = self.shaders.borrow();
+        let shader = shaders.get(pipeline.shader.0);
+        let uniforms = shader.uniforms.borrow();
+        let buffers = self.buffers.borrow();
+        let images = self.images.borrow();
+        let samplers = self.samplers.borrow();
+
+        // The target is borrowed before the textures, so that sampling the
+        // image being rendered to reads zeros instead of panicking.
+        let pass = self.pass(bound.pass);
+        let target = Target::new(self, pass.as_deref());
+        let textures = bound
+            .images
+            .iter()
+            .map(|(image, sampler)| {
+                let image = images.get(image.0);
+                Texture {
+                    desc: image.desc,
+                    sampler: *samplers.get(sampler.0),
+                    data: image.data.try_borrow().ok(),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let [x, y, width, height] = self.viewport.get();
+        let mut rasterizer = Rasterizer {
+            clip: [
+                x.max(0),
+                y.max(0),
+                (x + width).min(target.width as i32),
+                (y + height).min(target.height as i32),
+            ],
+            viewport: [x as f32, y as f32, width as f32, height as f32],
+            target,
+            pipeline,
+            shader: &shader.shader,
+            uniforms: &uniforms,
+            textures: &textures,
+        };
+
+        let index_data = index_type.map(|index_type| {
+            let buffer = bound.index_buffer.expect("No index buffer is bound");
+            (index_type, buffers.get(buffer.0).data.borrow())
+        });
+        let vertex_data = bound
+            .vertex_buffers
+            .iter()
+            .map(|(buffer, offset)| (buffers.get(buffer.0).data.borrow(), *offset))
+            .collect::<Vec<_>>();
+        let mut attributes = vec![[0.0; 4]; shader.shader.attributes.len()];
+        let mut vertices = Vec::with_capacity(count);
+        for instance in 0..instances {
+            vertices.clear();
+            for i in start..start + count {
+                let index = match &index_data {
+                    Some((index_type, data)) => read_index(data, *index_type, i),
+                    None => i,
+                };
+
+                for attribute in attributes.iter_mut() {
+                    *attribute = [0.0, 0.0, 0.0, 1.0];
+                }
+                for (attrs, (data, buffer_offset)) in bound.attrs.iter().zip(&vertex_data) {
+                    for attr in attrs {
+                        let element = match attr.divisor {
+                            0 => index,
+                            divisor => instance / divisor as usize,
+                        };
+                        let offset =
+                            buffer_offset + attr.offset as usize + attr.stride as usize * element;
+                        if let Some(attribute) = attributes.get_mut(attr.location as usize) {
+                            read_attribute(data, offset, attr.format, attribute);
+                        }
+                    }
+                }
+
+                vertices.push((shader.shader.vertex)(&VertexInput {
+                    attributes: &attributes,
+                    vertex_index: index,
+                    instance_index: instance,
+                    uniforms: &uniforms,
+                }));
+            }
+
+            match primitive {
+                PrimitiveType::Points => {
+                    for vertex in &vertices {
+                        rasterizer.point(vertex);
+                    }
+                }
+                PrimitiveType::Lines => {
+                    for line in vertices.chunks_exact(2) {
+                        rasterizer.line(&line[0], &line[1]);
+                    }
+                }
+                PrimitiveType::LineStrip => {
+                    for line in vertices.windows(2) {
+                        rasterizer.line(&line[0], &line[1]);
+                    }
+                }
+                PrimitiveType::Triangles => {
+                    for triangle in vertices.chunks_exact(3) {
+                        rasterizer.triangle([&triangle[0], &triangle[1], &triangle[2]]);
+                    }
+                }
+                PrimitiveType::TriangleStrip => {
+                    for (i, triangle) in vertices.windows(3).enumerate() {
+                        if i % 2 == 0 {
+                            rasterizer.triangle([&triangle[0], &triangle[1], &triangle[2]]);
+                        } else {
+                            rasterizer.triangle([&triangle[1], &triangle[0], &triangle[2]]);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn commit_frame(&self) {
+        *self.bound.borrow_mut() = BoundState::default();
+    }
+
+    fn uniform_buffer_offset_alignment(&self) -> usize {
+        1
+    }
+}
+
+fn read_index(data: &[u8], index_type: IndexType, i: usize) -> usize {
+    let offset = i * index_type.size();
+    match index_type {
+        IndexType::U8 => data[offset] as usize,
+        IndexType::U16 => u16::from_ne_bytes([data[offset], data[offset + 1]]) as usize,
+        IndexType::U32 => u32::from_ne_bytes([
+            data[offset],
+            data[offset + 1],
+            data[offset + 2],
+            data[offset + 3],
+        ]) as usize,
+    }
+}
+
+fn read_attribute(data: &[u8], offset: usize, format: VertexFormat, out: &mut [f32; 4]) {
+    let (count, size) = match format {
+        VertexFormat::Float1 => (1, 4),
+        VertexFormat::Float2 => (2, 4),
+        VertexFormat::Float3 => (3, 4),
+        VertexFormat::Float4 => (4, 4),
+        VertexFormat::Byte1 => (1, 1),
+        VertexFormat::Byte2 => (2, 1),
+        VertexFormat::Byte3 => (3, 1),
+        VertexFormat::Byte4 => (4, 1),
+        VertexFormat::Short1 => (1, 2),
+        VertexFormat::Short2 => (2, 2),
+        VertexFormat::Short3 => (3, 2),
+        VertexFormat::Short4 => (4, 2),
+    };
+    let bytes = match data.get(offset..offset + count * size) {
+        Some(bytes) => bytes,
+        None => return,
+    };
+    for (value, bytes) in out.iter_mut().zip(bytes.chunks_exact(size)) {
+        *value = match *bytes {
+            [a, b, c, d] => f32::from_ne_bytes([a, b, c, d]),
+            [a, b] => i16::from_ne_bytes([a, b]) as f32,
+            [a] => a as i8 as f32,
+            _ => unreachable!(),
+        };
+    }
+}
+
+/// The color and depth/stencil buffers being rendered to.
+struct Target<'a> {
+    width: u32,
+    height: u32,
+    color: Option<(ImageFormat, RefMut<'a, Vec<u8>>)>,
+    depth_stencil: Option<(ImageFormat, RefMut<'a, Vec<u8>>)>,
+}
+
+impl<'a> Target<'a> {
+    fn new(backend: &'a SoftwareBackend, pass: Option<&'a SwPass>) -> Self {
+        match pass {
+            Some(pass) => {
+                let attachment = |(format, data): &'a (ImageFormat, Rc<RefCell<Vec<u8>>>)| {
+                    (*format, data.borrow_mut())
+                };
+                Self {
+                    width: pass.width,
+                    height: pass.height,
+                    color: pass.color.as_ref().map(attachment),
+                    depth_stencil: pass.depth_stencil.as_ref().map(attachment),
+                }
+            }
+            None => Self {
+                width: backend.width,
+                height: backend.height,
+                color: Some((ImageFormat::Rgba8, backend.color.borrow_mut())),
+                depth_stencil: Some((
+                    ImageFormat::Depth24Stencil8,
+                    backend.depth_stencil.borrow_mut(),
+                )),
+            },
+        }
+    }
+}
+
+/// A sampled image.
+struct Texture<'a> {
+    desc: ImageDesc,
+    sampler: SamplerState,
+    data: Option<Ref<'a, Vec<u8>>>,
+}
+
+impl Texture<'_> {
+    /// Samples the texture. The third coordinate is the layer index of 2D,
+    /// cube and array images, and the normalized depth of 3D images.
+    fn sample(&self, coord: [f32; 3], lod: f32) -> [f32; 4] {
+        self.filter(coord, lod, |data, level, x, y, z| {
+            self.texel(data, level, x, y, z)
+        })
+    }
+
+    fn sample_compare(&self, coord: [f32; 3], reference: f32) -> f32 {
+        let func = match self.sampler.compare {
+            Some(func) => func,
+            None => return 0.0,
+        };
+        // Like GL, each texel is compared before filtering.
+        self.filter(coord, 0.0, |data, level, x, y, z| {
+            let passed = compare(func, reference, self.texel(data, level, x, y, z)[0]);
+            [if passed { 1.0 } else { 0.0 }; 4]
+        })[0]
+    }
+
+    fn filter(
+        &self,
+        coord: [f32; 3],
+        lod: f32,
+        fetch: impl Fn(&[u8], u32, i32, i32, i32) -> [f32; 4],
+    ) -> [f32; 4] {
+        let data = match &self.data {
+            Some(data) => data,
+            None => return [0.0; 4],
+        };
+        let sampler = &self.sampler;
+        let lod = (lod + sampler.lod_bias).clamp(sampler.min_lod, sampler.max_lod);
+        if lod <= 0.0 {
+            let linear = sampler.mag_filter == ImageFilter::Linear;
+            return self.filter_level(data, 0, linear, coord, &fetch);
+        }
+
+        let linear = sampler.min_filter == ImageFilter::Linear;
+        let lod = lod.min((self.desc.mip_count - 1) as f32);
+        match sampler.mipmap_filter {
+            None => self.filter_level(data, 0, linear, coord, &fetch),
+            Some(ImageFilter::Nearest) => {
+                let level = floor(lod + 0.5) as u32;
+                self.filter_level(data, level, linear, coord, &fetch)
+            }
+            Some(ImageFilter::Linear) => {
+                let level = floor(lod) as u32;
+                let t = lod - level as f32;
+                let a = self.filter_level(data, level, linear, coord, &fetch);
+                if t == 0.0 {
+                    a
+                } else {
+                    let b = self.filter_level(data, level + 1, linear, coord, &fetch);
+                    lerp4(a, b, t)
+                }
+            }
+        }
+    }
+
+    fn filter_level(
+        &self,
+        data: &[u8],
+        level: u32,
+        linear: bool,
+        [u, v, w]: [f32; 3],
+        fetch: &impl Fn(&[u8], u32, i32, i32, i32) -> [f32; 4],
+    ) -> [f32; 4] {
+        let (width, height, layers) = self.desc.level_size(level);
+        let x = u * width as f32;
+        let y = v * height as f32;
+        let filter_layer = |z: i32| {
+            if !linear {
+                return fetch(data, level, floor(x), floor(y), z);
+            }
+            let (x, y) = (x - 0.5, y - 0.5);
+            let (x0, y0) = (floor(x), floor(y));
+            let (fx, fy) = (x - x0 as f32, y - y0 as f32);
+            let top = lerp4(
+                fetch(data, level, x0, y0, z),
+                fetch(data, level, x0 + 1, y0, z),
+                fx,
+            );
+            let bottom = lerp4(
+                fetch(data, level, x0, y0 + 1, z),
+                fetch(data, level, x0 + 1, y0 + 1, z),
+                fx,
+            );
+            lerp4(top, bottom, fy)
+        };
+
+        match self.desc.kind {
+            ImageKind::D3 { .. } => {
+                let z = w * layers as f32;
+                if !linear {
+                    return filter_layer(floor(z));
+                }
+                let z = z - 0.5;
+                let z0 = floor(z);
+                lerp4(filter_layer(z0), filter_layer(z0 + 1), z - z0 as f32)
+            }
+            _ => filter_layer((w as i32).clamp(0, layers as i32 - 1)),
+        }
+    }
+
+    fn texel(&self, data: &[u8], level: u32, x: i32, y: i32, z: i32) -> [f32; 4] {
+        let (width, height, layers) = self.desc.level_size(level);
+        // Like GL, cube map faces are always clamped to their edges, and only
+        // 3D images wrap between layers.
+        let (wrap_u, wrap_v, wrap_w) = match self.desc.kind {
+            ImageKind::Cube => (ImageWrap::Clamp, ImageWrap::Clamp, ImageWrap::Clamp),
+            ImageKind::D3 { .. } => (
+                self.sampler.wrap_u,
+                self.sampler.wrap_v,
+                self.sampler.wrap_w,
+            ),
+            _ => (self.sampler.wrap_u, self.sampler.wrap_v, ImageWrap::Clamp),
+        };
+        let (x, y, z) = match (
+            wrap(x, width, wrap_u),
+            wrap(y, height, wrap_v),
+            wrap(z, layers, wrap_w),
+        ) {
+            (Some(x), Some(y), Some(z)) => (x, y, z),
+            _ => {
+                let (r, g, b, a) = self.sampler.border_color;
+                return [r, g, b, a];
+            }
+        };
+        let size = self.desc.format.size();
+        let offset = level_offset(&self.desc, level)
+            + ((z * height as usize + y) * width as usize + x) * size;
+        read_color(self.desc.format, &data[offset..offset + size])
+    }
+}
+
+/// Returns the byte offset of a mip level in an image's data.
+fn level_offset(desc: &ImageDesc, level: u32) -> usize {
+    (0..level).map(|level| desc.level_len(level)).sum()
+}
+
+/// Returns the texture coordinate and face index of a direction into a cube
+/// map, following the GL face selection rules.
+fn cube_coord([x, y, z]: [f32; 3]) -> [f32; 3] {
+    let (ax, ay, az) = (abs(x), abs(y), abs(z));
+    let (face, s, t, major) = if ax >= ay && ax >= az {
+        if x >= 0.0 {
+            (0, -z, -y, ax)
+        } else {
+            (1, z, -y, ax)
+        }
+    } else if ay >= az {
+        if y >= 0.0 {
+            (2, x, z, ay)
+        } else {
+            (3, x, -z, ay)
+        }
+    } else if z >= 0.0 {
+        (4, x, -y, az)
+    } else {
+        (5, -x, -y, az)
+    };
+    [
+        (s / major + 1.0) * 0.5,
+        (t / major + 1.0) * 0.5,
+        face as f32,
+    ]
+}
+
+/// Wraps a texel coordinate, or returns `None` if it lies in the border.
+fn wrap(coord: i32, size: u32, wrap: ImageWrap) -> Option<usize> {
+    let size = size as i32;
+    let coord = match wrap {
+        ImageWrap::Clamp => coord.clamp(0, size - 1),
+        ImageWrap::Repeat => coord.rem_euclid(size),
+        ImageWrap::MirroredRepeat => {
+            let coord = coord.rem_euclid(size * 2);
+            if coord < size {
+                coord
+            } else {
+                size * 2 - 1 - coord
+            }
+        }
+        ImageWrap::ClampToBorder if !(0..size).contains(&coord) => return None,
+        ImageWrap::ClampToBorder => coord,
+    };
+    Some(coord as usize)
+}
+
+/// The type of each component of an image format.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Component {
+    Unorm8,
+    Srgb8,
+    F16,
+    F32,
+    U8,
+    U16,
+    U32,
+    I32,
+    D16,
+    D24,
+    D32F,
+    D24S8,
+}
+
+impl Component {
+    fn size(self) -> usize {
+        match self {
+            Component::Unorm8 | Component::Srgb8 | Component::U8 => 1,
+            Component::F16 | Component::U16 | Component::D16 => 2,
+            Component::F32
+            | Component::U32
+            | Component::I32
+            | Component::D24
+            | Component::D32F
+            | Component::D24S8 => 4,
+        }
+    }
+
+    fn is_normalized(self) -> bool {
+        matches!(
+            self,
+            Component::Unorm8
+                | Component::Srgb8
+                | Component::D16
+                | Component::D24
+                | Component::D24S8
+        )
+    }
+}
+
+/// Returns the number of components and the component type of a format.
+fn format_layout(format: ImageFormat) -> (usize, Component) {
+    match format {
+        ImageFormat::R8 => (1, Component::Unorm8),
+        ImageFormat::Rg8 => (2, Component::Unorm8),
+        ImageFormat::Rgb8 => (3, Component::Unorm8),
+        ImageFormat::Rgba8 => (4, Component::Unorm8),
+        ImageFormat::Srgb8 => (3, Component::Srgb8),
+        ImageFormat::Srgba8 => (4, Component::Srgb8),
+        ImageFormat::R16F => (1, Component::F16),
+        ImageFormat::Rgba16F => (4, Component::F16),
+        ImageFormat::R32F => (1, Component::F32),
+        ImageFormat::Rgba32F => (4, Component::F32),
+        ImageFormat::R8Ui => (1, Component::U8),
+        ImageFormat::Rgba8Ui => (4, Component::U8),
+        ImageFormat::R16Ui => (1, Component::U16),
+        ImageFormat::R32Ui => (1, Component::U32),
+        ImageFormat::R32I => (1, Component::I32),
+        ImageFormat::Rgba32Ui => (4, Component::U32),
+        ImageFormat::Depth16 => (1, Component::D16),
+        ImageFormat::Depth24 => (1, Component::D24),
+        ImageFormat::Depth32F => (1, Component::D32F),
+        ImageFormat::Depth24Stencil8 => (1, Component::D24S8),
+    }
+}
+
+/// Reads a pixel, filling in missing components from `(0, 0, 0, 1)`.
+/// Integer components are converted to their value as a float.
+fn read_color(format: ImageFormat, pixel: &[u8]) -> [f32; 4] {
+    let (channels, component) = format_layout(format);
+    let mut color = [0.0, 0.0, 0.0, 1.0];
+    for (i, (value, bytes)) in color
+        .iter_mut()
+        .zip(pixel.chunks_exact(component.size()))
+        .take(channels)
+        .enumerate()
+    {
+        *value = match component {
+            Component::Unorm8 => bytes[0] as f32 / 255.0,
+            Component::Srgb8 if i < 3 => srgb_to_linear(bytes[0] as f32 / 255.0),
+            Component::Srgb8 => bytes[0] as f32 / 255.0,
+            Component::F16 => f16_to_f32(u16::from_ne_bytes([bytes[0], bytes[1]])),
+            Component::F32 => f32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            Component::U8 => bytes[0] as f32,
+            Component::U16 => u16::from_ne_bytes([bytes[0], bytes[1]]) as f32,
+            Component::U32 => u32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f32,
+            Component::I32 => i32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f32,
+            Component::D16 => u16::from_ne_bytes([bytes[0], bytes[1]]) as f32 / 65535.0,
+            Component::D24 | Component::D24S8 => {
+                let value = u32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+                (value >> 8) as f32 / 16_777_215.0
+            }
+            Component::D32F => f32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+        };
+    }
+    color
+}
+
+fn write_color(format: ImageFormat, color: [f32; 4], pixel: &mut [u8]) {
+    let (channels, component) = format_layout(format);
+    let unorm = |value: f32| (value.clamp(0.0, 1.0) * 255.0 + 0.5) as u8;
+    for (i, (value, bytes)) in color
+        .iter()
+        .zip(pixel.chunks_exact_mut(component.size()))
+        .take(channels)
+        .enumerate()
+    {
+        match component {
+            Component::Unorm8 => bytes[0] = unorm(*value),
+            Component::Srgb8 if i < 3 => bytes[0] = unorm(linear_to_srgb(*value)),
+            Component::Srgb8 => bytes[0] = unorm(*value),
+            Component::F16 => bytes.copy_from_slice(&f32_to_f16(*value).to_ne_bytes()),
+            Component::F32 => bytes.copy_from_slice(&value.to_ne_bytes()),
+            Component::U8 => bytes[0] = *value as u8,
+            Component::U16 => bytes.copy_from_slice(&(*value as u16).to_ne_bytes()),
+            Component::U32 => bytes.copy_from_slice(&(*value as u32).to_ne_bytes()),
+            Component::I32 => bytes.copy_from_slice(&(*value as i32).to_ne_bytes()),
+            Component::D16 => {
+                let depth = (value.clamp(0.0, 1.0) * 65535.0 + 0.5) as u16;
+                bytes.copy_from_slice(&depth.to_ne_bytes());
+            }
+            Component::D24 | Component::D24S8 => {
+                let depth = (value.clamp(0.0, 1.0) as f64 * 16_777_215.0 + 0.5) as u32;
+                let stored = u32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+                // The low byte holds the stencil value, or replicates the
+                // high bits of the depth value.
+                let low = match component {
+                    Component::D24S8 => stored & 0xff,
+                    _ => depth >> 16,
+                };
+                bytes.copy_from_slice(&((depth << 8) | low).to_ne_bytes());
+            }
+            Component::D32F => bytes.copy_from_slice(&value.to_ne_bytes()),
+        }
+    }
+}
+
+fn read_stencil(pixel: &[u8]) -> u8 {
+    u32::from_ne_bytes([pixel[0], pixel[1], pixel[2], pixel[3]]) as u8
+}
+
+fn write_stencil(format: ImageFormat, stencil: u8, pixel: &mut [u8]) {
+    if format == ImageFormat::Depth24Stencil8 {
+        let stored = u32::from_ne_bytes([pixel[0], pixel[1], pixel[2], pixel[3]]);
+        pixel.copy_from_slice(&((stored & !0xff) | stencil as u32).to_ne_bytes());
+    }
+}
+
+fn f16_to_f32(bits: u16) -> f32 {
+    let sign = ((bits >> 15) as u32) << 31;
+    let exponent = ((bits >> 10) & 0x1f) as u32;
+    let mantissa = (bits & 0x3ff) as u32;
+    let magnitude = match exponent {
+        0 => mantissa as f32 / (1 << 24) as f32,
+        0x1f => f32::from_bits(0x7f80_0000 | (mantissa << 13)),
+        _ => f32::from_bits(((exponent + 112) << 23) | (mantissa << 13)),
+    };
+    f32::from_bits(magnitude.to_bits() | sign)
+}
+
+fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7f_ffff;
+    if exponent == 0xff {
+        let nan = if mantissa != 0 { 0x200 } else { 0 };
+        return sign | 0x7c00 | nan;
+    }
+
+    let exponent = exponent - 127 + 15;
+    if exponent >= 0x1f {
+        sign | 0x7c00
+    } else if exponent <= 0 {
+        if exponent < -10 {
+            return sign;
+        }
+        let shift = (14 - exponent) as u32;
+        let mantissa = mantissa | 0x80_0000;
+        sign | round_shift(mantissa, shift) as u16
+    } else {
+        // Rounding may carry into the exponent, which is still correct.
+        sign | round_shift(((exponent as u32) << 23) | mantissa, 13) as u16
+    }
+}
+
+/// Shifts `value` right by `shift` bits, rounding to nearest with ties to
+/// even.
+fn round_shift(value: u32, shift: u32) -> u32 {
+    let half = 1 << (shift - 1);
+    let rest = value & ((1 << shift) - 1);
+    let shifted = value >> shift;
+    if rest > half || (rest == half && shifted & 1 == 1) {
+        shifted + 1
+    } else {
+        shifted
+    }
+}
+
+fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        powf((value + 0.055) / 1.055, 2.4)
+    }
+}
+
+fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.003_130_8 {
+        value * 12.92
+    } else {
+        1.055 * powf(value, 1.0 / 2.4) - 0.055
+    }
+}
+
+/// Computes `base` raised to `exponent` for a positive `base`, since `core`
+/// has no `powf`.
+fn powf(base: f32, exponent: f32) -> f32 {
+    if base <= 0.0 {
+        return 0.0;
+    }
+
+    // log2(base) = e + log2(m) with m in [1, 2), using the series
+    // ln(m) = 2 * atanh((m - 1) / (m + 1)).
+    let bits = base.to_bits();
+    let e = ((bits >> 23) & 0xff) as i32 - 127;
+    let m = f32::from_bits((bits & 0x7f_ffff) | 0x3f80_0000);
+    let s = (m - 1.0) / (m + 1.0);
+    let s2 = s * s;
+    let ln_m = 2.0 * s * (1.0 + s2 * (1.0 / 3.0 + s2 * (1.0 / 5.0 + s2 * (1.0 / 7.0 + s2 / 9.0))));
+    let log2 = e as f32 + ln_m * f32::consts::LOG2_E;
+
+    // 2^x = 2^n * e^(f * ln(2)) with n an integer and f in [0, 1).
+    let x = exponent * log2;
+    let n = floor(x);
+    let f = (x - n as f32) * f32::consts::LN_2;
+    let (mut term, mut sum) = (1.0, 1.0);
+    for i in 1..10 {
+        term *= f / i as f32;
+        sum += term;
+    }
+    sum * f32::from_bits(((n + 127).clamp(1, 254) as u32) << 23)
+}
+
+/// A vertex after the viewport transform.
+struct WindowVertex<'a> {
+    x: f32,
+    y: f32,
+    z: f32,
+    inv_w: f32,
+    varyings: &'a [f32],
+}
+
+struct Rasterizer<'a> {
+    target: Target<'a>,
+    /// The pixel bounds `[x0, y0, x1, y1)` fragments are generated in.
+    clip: [i32; 4],
+    viewport: [f32; 4],
+    pipeline: &'a SwPipeline,
+    shader: &'a SoftwareShader,
+    uniforms: &'a [u8],
+    textures: &'a [Texture<'a>],
+}
+
+impl Rasterizer<'_> {
+    fn to_window<'v>(&self, vertex: &'v VertexOutput) -> Option<WindowVertex<'v>> {
+        let [x, y, z, w] = vertex.position;
+        if w <= 0.0 {
+            return None;
+        }
+        let inv_w = 1.0 / w;
+        let [vx, vy, width, height] = self.viewport;
+        Some(WindowVertex {
+            x: vx + (x * inv_w + 1.0) * 0.5 * width,
+            y: vy + (y * inv_w + 1.0) * 0.5 * height,
+            z: (z * inv_w + 1.0) * 0.5,
+            inv_w,
+            varyings: &vertex.varyings,
+        })
+    }
+
+    fn point(&mut self, vertex: &VertexOutput) {
+        if !in_depth_range(&vertex.position) {
+            return;
+        }
+        if let Some(v) = self.to_window(vertex) {
+            self.fragment(floor(v.x), floor(v.y), v.z, v.inv_w, true, v.varyings);
+        }
+    }
+
+    fn line(&mut self, a: &VertexOutput, b: &VertexOutput) {
+        let clipped = clip_polygon(vec![a.clone(), b.clone()]);
+        let (a, b) = match (clipped.first(), clipped.last()) {
+            (Some(a), Some(b)) if clipped.len() >= 2 => (a, b),
+            _ => return,
+        };
+        let (a, b) = match (self.to_window(a), self.to_window(b)) {
+            (Some(a), Some(b)) => (a, b),
+            _ => return,
+        };
+
+        let (dx, dy) = (b.x - a.x, b.y - a.y);
+        let steps = floor(abs(dx).max(abs(dy)) + 0.5).max(1);
+        let mut varyings = Vec::new();
+        // The last pixel is left out so that line strips do not draw shared
+        // vertices twice.
+        for step in 0..steps {
+            let t = step as f32 / steps as f32;
+            let (x, y) = (a.x + dx * t, a.y + dy * t);
+            let z = a.z + (b.z - a.z) * t;
+            let (wa, wb) = ((1.0 - t) * a.inv_w, t * b.inv_w);
+            let inv_w = wa + wb;
+            interpolate(
+                &mut varyings,
+                &[(a.varyings, wa / inv_w), (b.varyings, wb / inv_w)],
+            );
+            self.fragment(floor(x), floor(y), z, inv_w, true, &varyings);
+        }
+    }
+
+    fn triangle(&mut self, vertices: [&VertexOutput; 3]) {
+        let clipped = clip_polygon(vertices.iter().map(|&v| v.clone()).collect());
+        if clipped.len() < 3 {
+            return;
+        }
+        let window = clipped
+            .iter()
+            .map(|v| self.to_window(v))
+            .collect::<Option<Vec<_>>>();
+        if let Some(window) = window {
+            for i in 1..window.len() - 1 {
+                self.window_triangle(&window[0], &window[i], &window[i + 1]);
+            }
+        }
+    }
+
+    fn window_triangle(&mut self, v0: &WindowVertex, v1: &WindowVertex, v2: &WindowVertex) {
+        let area = edge(v0, v1, v2.x, v2.y);
+        if area == 0.0 {
+            return;
+        }
+        let front_facing = (area > 0.0) == (self.pipeline.raster.front_face == FrontFace::Ccw);
+        match self.pipeline.raster.cull {
+            CullMode::Front if front_facing => return,
+            CullMode::Back if !front_facing => return,
+            _ => {}
+        }
+        // Make the winding counter-clockwise so that inside is positive.
+        let (v1, v2, area) = if area < 0.0 {
+            (v2, v1, -area)
+        } else {
+            (v1, v2, area)
+        };
+
+        let bias = if self.pipeline.raster.depth_bias_enabled() {
+            let dzdx = ((v1.z - v0.z) * (v2.y - v0.y) - (v2.z - v0.z) * (v1.y - v0.y)) / area;
+            let dzdy = ((v2.z - v0.z) * (v1.x - v0.x) - (v1.z - v0.z) * (v2.x - v0.x)) / area;
+            abs(dzdx).max(abs(dzdy)) * self.pipeline.raster.depth_bias_slope
+                + self.pipeline.raster.depth_bias / (1 << 24) as f32
+        } else {
+            0.0
+        };
+
+        let [clip_x0, clip_y0, clip_x1, clip_y1] = self.clip;
+        let x0 = floor(v0.x.min(v1.x).min(v2.x)).max(clip_x0);
+        let y0 = floor(v0.y.min(v1.y).min(v2.y)).max(clip_y0);
+        let x1 = (floor(v0.x.max(v1.x).max(v2.x)) + 1).min(clip_x1);
+        let y1 = (floor(v0.y.max(v1.y).max(v2.y)) + 1).min(clip_y1);
+
+        let mut varyings = Vec::new();
+        for y in y0..y1 {
+            for x in x0..x1 {
+                let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+                let w0 = edge(v1, v2, px, py);
+                let w1 = edge(v2, v0, px, py);
+                let w2 = edge(v0, v1, px, py);
+                if !(covers(w0, v1, v2) && covers(w1, v2, v0) && covers(w2, v0, v1)) {
+                    continue;
+                }
+
+                let (b0, b1, b2) = (w0 / area, w1 / area, w2 / area);
+                let z = b0 * v0.z + b1 * v1.z + b2 * v2.z + bias;
+                let (p0, p1, p2) = (b0 * v0.inv_w, b1 * v1.inv_w, b2 * v2.inv_w);
+                let inv_w = p0 + p1 + p2;
+                interpolate(
+                    &mut varyings,
+                    &[
+                        (v0.varyings, p0 / inv_w),
+                        (v1.varyings, p1 / inv_w),
+                        (v2.varyings, p2 / inv_w),
+                    ],
+                );
+                self.fragment(x, y, z, inv_w, front_facing, &varyings);
+            }
+        }
+    }
+
+    fn fragment(
+        &mut self,
+        x: i32,
+        y: i32,
+        z: f32,
+        inv_w: f32,
+        front_facing: bool,
+        varyings: &[f32],
+    ) {
+        let [clip_x0, clip_y0, clip_x1, clip_y1] = self.clip;
+        if x < clip_x0 || y < clip_y0 || x >= clip_x1 || y >= clip_y1 {
+            return;
+        }
+        let pixel = y as usize * self.target.width as usize + x as usize;
+        let z = z.clamp(0.0, 1.0);
+
+        let depth = self.pipeline.depth;
+        // The depth is quantized to the precision of the depth buffer before
+        // it is tested, keeping the stored stencil value.
+        let mut depth_bytes = [0; 4];
+        let (depth_enabled, depth_passed) = match &self.target.depth_stencil {
+            Some((format, pixels)) if depth.enabled() => {
+                let size = format.size();
+                let stored = &pixels[pixel * size..(pixel + 1) * size];
+                let quantized = &mut depth_bytes[..size];
+                quantized.copy_from_slice(stored);
+                write_color(*format, [z; 4], quantized);
+                let value = read_color(*format, quantized)[0];
+                (
+                    true,
+                    compare(depth.compare, value, read_color(*format, stored)[0]),
+                )
+            }
+            _ => (false, true),
+        };
+
+        // The stencil test always passes without a stencil buffer. The depth
+        // test is skipped for fragments failing the stencil test.
+        let stencil = match (&self.target.depth_stencil, self.pipeline.stencil) {
+            (Some((ImageFormat::Depth24Stencil8, pixels)), Some(stencil)) => {
+                let stored = read_stencil(&pixels[pixel * 4..(pixel + 1) * 4]);
+                let face = if front_facing {
+                    stencil.front
+                } else {
+                    stencil.back
+                };
+                let mask = stencil.read_mask;
+                let (passed, op) = if !compare(
+                    face.compare,
+                    (stencil.reference & mask) as f32,
+                    (stored & mask) as f32,
+                ) {
+                    (false, face.fail_op)
+                } else if !depth_passed {
+                    (false, face.depth_fail_op)
+                } else {
+                    (true, face.pass_op)
+                };
+                Some((stencil, stored, passed, op))
+            }
+            _ => None,
+        };
+        let passed = match stencil {
+            Some((_, _, passed, _)) => passed,
+            None => depth_passed,
+        };
+        // Discarded fragments leave the stencil buffer untouched, so the
+        // shader still runs for failing fragments that would update it.
+        if !passed && !matches!(stencil, Some((.., op)) if op != StencilOp::Keep) {
+            return;
+        }
+
+        let color = (self.shader.fragment)(&FragmentInput {
+            position: [x as f32 + 0.5, y as f32 + 0.5, z, inv_w],
+            front_facing,
+            varyings,
+            uniforms: self.uniforms,
+            images: self.textures,
+        });
+        let color = match color {
+            Some(color) => color,
+            None => return,
+        };
+
+        if passed && depth_enabled && depth.write {
+            let (format, pixels) = self.target.depth_stencil.as_mut().unwrap();
+            let size = format.size();
+            pixels[pixel * size..(pixel + 1) * size].copy_from_slice(&depth_bytes[..size]);
+        }
+        // The stencil value is written after the depth value, which carries
+        // the previously stored stencil value.
+        if let Some((stencil, stored, _, op)) = stencil {
+            let (format, pixels) = self.target.depth_stencil.as_mut().unwrap();
+            let value = stencil_op(op, stored, stencil.reference);
+            let value = (stored & !stencil.write_mask) | (value & stencil.write_mask);
+            write_stencil(*format, value, &mut pixels[pixel * 4..(pixel + 1) * 4]);
+        }
+        if !passed {
+            return;
+        }
+
+        if let Some((format, pixels)) = &mut self.target.color {
+            let size = format.size();
+            let dest = &mut pixels[pixel * size..(pixel + 1) * size];
+            let normalized = format_layout(*format).1.is_normalized();
+            // Blending does not apply to integer formats.
+            let color = match self.pipeline.blend {
+                Some(blend) if !format.is_integer() => {
+                    let alpha = self.pipeline.alpha_blend.unwrap_or(blend);
+                    let src = if normalized { clamp4(color) } else { color };
+                    let dst = read_color(*format, dest);
+                    let constant = self.pipeline.blend_color;
+                    let mut out = [0.0; 4];
+                    for (i, value) in out.iter_mut().enumerate() {
+                        let state = if i < 3 { blend } else { alpha };
+                        let s = src[i] * blend_factor(state.source, i, src, dst, constant);
+                        let d = dst[i] * blend_factor(state.dest, i, src, dst, constant);
+                        *value = match state.op {
+                            BlendOp::Add => s + d,
+                            BlendOp::Subtract => s - d,
+                            BlendOp::ReverseSubtract => d - s,
+                            BlendOp::Min => src[i].min(dst[i]),
+                            BlendOp::Max => src[i].max(dst[i]),
+                        };
+                    }
+                    out
+                }
+                _ => color,
+            };
+            write_color(*format, color, dest);
+        }
+    }
+}
+
+/// Clips a polygon in clip space against the near and far planes.
+fn clip_polygon(mut polygon: Vec<VertexOutput>) -> Vec<VertexOutput> {
+    let planes: [fn(&[f32; 4]) -> f32; 2] = [|p| p[2] + p[3], |p| p[3] - p[2]];
+    for plane in planes.iter() {
+        if polygon.iter().all(|v| plane(&v.position) >= 0.0) {
+            continue;
+        }
+        let n = polygon.len();
+        // A line is not closed, so its last vertex has no outgoing edge.
+        let edges = if n == 2 { 1 } else { n };
+        let mut clipped = Vec::with_capacity(n + 1);
+        for i in 0..n {
+            let a = &polygon[i];
+            let da = plane(&a.position);
+            if da >= 0.0 {
+                clipped.push(a.clone());
+            }
+            if i < edges {
+                let b = &polygon[(i + 1) % n];
+                let db = plane(&b.position);
+                if (da >= 0.0) != (db >= 0.0) {
+                    clipped.push(lerp_vertex(a, b, da / (da - db)));
+                }
+            }
+        }
+        polygon = clipped;
+    }
+    polygon
+}
+
+fn in_depth_range(p: &[f32; 4]) -> bool {
+    p[2] + p[3] >= 0.0 && p[3] - p[2] >= 0.0
+}
+
+fn lerp_vertex(a: &VertexOutput, b: &VertexOutput, t: f32) -> VertexOutput {
+    let mut position = [0.0; 4];
+    for (i, value) in position.iter_mut().enumerate() {
+        *value = a.position[i] + (b.position[i] - a.position[i]) * t;
+    }
+    VertexOutput {
+        position,
+        varyings: a
+            .varyings
+            .iter()
+            .zip(&b.varyings)
+            .map(|(a, b)| a + (b - a) * t)
+            .collect(),
+    }
+}
+
+/// Computes the weighted sum of each varying into `out`.
+fn interpolate(out: &mut Vec<f32>, inputs: &[(&[f32], f32)]) {
+    let len = inputs.iter().map(|(v, _)| v.len()).min().unwrap_or(0);
+    out.clear();
+    out.extend((0..len).map(|i| inputs.iter().map(|(v, weight)| v[i] * weight).sum::<f32>()));
+}
+
+/// Returns twice the signed area of the triangle `a`, `b`, `(x, y)`, which is
+/// positive if the point lies to the left of the edge from `a` to `b`.
+fn edge(a: &WindowVertex, b: &WindowVertex, x: f32, y: f32) -> f32 {
+    (b.x - a.x) * (y - a.y) - (b.y - a.y) * (x - a.x)
+}
+
+/// Whether a pixel center with edge function value `w` is covered by the edge
+/// from `a` to `b`, following the top-left rule for centers on the edge.
+fn covers(w: f32, a: &WindowVertex, b: &WindowVertex) -> bool {
+    if w != 0.0 {
+        return w > 0.0;
+    }
+    let (dx, dy) = (b.x - a.x, b.y - a.y);
+    (dy == 0.0 && dx < 0.0) || dy < 0.0
+}
+
+fn compare(func: CompareFunc, value: f32, stored: f32) -> bool {
+    match func {
+        CompareFunc::Never => false,
+        CompareFunc::Less => value < stored,
+        CompareFunc::Equal => value == stored,
+        CompareFunc::LessEqual => value <= stored,
+        CompareFunc::Greater => value > stored,
+        CompareFunc::NotEqual => value != stored,
+        CompareFunc::GreaterEqual => value >= stored,
+        CompareFunc::Always => true,
+    }
+}
+
+fn stencil_op(op: StencilOp, stored: u8, reference: u8) -> u8 {
+    match op {
+        StencilOp::Keep => stored,
+        StencilOp::Zero => 0,
+        StencilOp::Replace => reference,
+        StencilOp::IncrementClamp => stored.saturating_add(1),
+        StencilOp::DecrementClamp => stored.saturating_sub(1),
+        StencilOp::Invert => !stored,
+        StencilOp::IncrementWrap => stored.wrapping_add(1),
+        StencilOp::DecrementWrap => stored.wrapping_sub(1),
+    }
+}
+
+fn blend_factor(
+    factor: BlendFactor,
+    i: usize,
+    src: [f32; 4],
+    dst: [f32; 4],
+    constant: [f32; 4],
+) -> f32 {
+    match factor {
+        BlendFactor::Zero => 0.0,
+        BlendFactor::One => 1.0,
+        BlendFactor::SourceColor => src[i],
+        BlendFactor::OneMinusSourceColor => 1.0 - src[i],
+        BlendFactor::SourceAlpha => src[3],
+        BlendFactor::OneMinusSourceAlpha => 1.0 - src[3],
+        BlendFactor::DestColor => dst[i],
+        BlendFactor::OneMinusDestColor => 1.0 - dst[i],
+        BlendFactor::DestAlpha => dst[3],
+        BlendFactor::OneMinusDestAlpha => 1.0 - dst[3],
+        BlendFactor::ConstantColor => constant[i],
+        BlendFactor::OneMinusConstantColor => 1.0 - constant[i],
+        BlendFactor::ConstantAlpha => constant[3],
+        BlendFactor::OneMinusConstantAlpha => 1.0 - constant[3],
+        BlendFactor::SourceAlphaSaturate if i < 3 => src[3].min(1.0 - dst[3]),
+        BlendFactor::SourceAlphaSaturate => 1.0,
+    }
+}
+
+fn clamp4(color: [f32; 4]) -> [f32; 4] {
+    let mut out = color;
+    for value in out.iter_mut() {
+        *value = value.clamp(0.0, 1.0);
+    }
+    out
+}
+
+fn lerp4(a: [f32; 4], b: [f32; 4], t: f32) -> [f32; 4] {
+    let mut out = a;
+    for (value, b) in out.iter_mut().zip(&b) {
+        *value += (b - *value) * t;
+    }
+    out
+}
+
+fn floor(value: f32) -> i32 {
+    let truncated = value as i32;
+    if (truncated as f32) > value {
+        truncated - 1
+    } else {
+        truncated
+    }
+}
+
+fn abs(value: f32) -> f32 {
+    if value < 0.0 {
+        -value
+    } else {
+        value
+    }
+}
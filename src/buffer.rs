@@ -2,9 +2,8 @@ use alloc::rc::Rc;
 use alloc::slice;
 use core::mem;
 
-use glow::HasContext;
-
-use super::Context;
+use crate::backend::{Backend, BufferId};
+use crate::{Context, Error};
 
 /// Kinds of a GPU buffer.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -13,6 +12,36 @@ pub enum BufferKind {
     Vertex,
     /// An index buffer.
     Index,
+    /// A uniform buffer.
+    Uniform,
+}
+
+/// Types of the indices in an index buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndexType {
+    /// 8-bit unsigned indices.
+    U8,
+    /// 16-bit unsigned indices.
+    U16,
+    /// 32-bit unsigned indices.
+    U32,
+}
+
+impl Default for IndexType {
+    fn default() -> Self {
+        IndexType::U16
+    }
+}
+
+impl IndexType {
+    /// Returns the size of a single index in bytes.
+    pub fn size(self) -> usize {
+        match self {
+            IndexType::U8 => 1,
+            IndexType::U16 => 2,
+            IndexType::U32 => 4,
+        }
+    }
 }
 
 /// Usage hints for a GPU buffer.
@@ -30,43 +59,36 @@ pub enum BufferUsage {
 
 /// A GPU buffer.
 pub struct Buffer {
-    pub(crate) inner: glow::Buffer,
+    pub(crate) id: BufferId,
     pub(crate) kind: BufferKind,
-    size: usize,
-    ctx: Rc<glow::Context>,
+    pub(crate) size: usize,
+    usage: BufferUsage,
+    backend: Rc<dyn Backend>,
 }
 
 impl Buffer {
-    pub(crate) fn new(ctx: &Context, kind: BufferKind, usage: BufferUsage, size: usize) -> Self {
-        assert_ne!(
-            usage,
-            BufferUsage::Static,
-            "Static buffers must be initialized with data"
-        );
+    pub(crate) fn new(
+        ctx: &Context,
+        kind: BufferKind,
+        usage: BufferUsage,
+        size: usize,
+    ) -> Result<Self, Error> {
+        if usage == BufferUsage::Static {
+            return Err(Error::MissingData);
+        }
+        if size == 0 {
+            return Err(Error::InvalidSize);
+        }
+
+        let id = ctx.backend.create_buffer(kind, usage, size, None)?;
 
-        let target = match kind {
-            BufferKind::Vertex => glow::ARRAY_BUFFER,
-            BufferKind::Index => glow::ELEMENT_ARRAY_BUFFER,
-        };
-        let usage = match usage {
-            BufferUsage::Static => glow::STATIC_DRAW,
-            BufferUsage::Dynamic => glow::DYNAMIC_DRAW,
-            BufferUsage::Stream => glow::STREAM_DRAW,
-        };
-
-        let inner = unsafe {
-            let buffer = ctx.inner.create_buffer().unwrap();
-            ctx.inner.bind_buffer(target, Some(buffer));
-            ctx.inner.buffer_data_size(target, size as _, usage);
-            buffer
-        };
-
-        Self {
-            inner,
+        Ok(Self {
+            id,
             kind,
             size,
-            ctx: ctx.inner.clone(),
-        }
+            usage,
+            backend: ctx.backend.clone(),
+        })
     }
 
     pub(crate) fn with_data<T>(
@@ -74,65 +96,81 @@ impl Buffer {
         kind: BufferKind,
         usage: BufferUsage,
         data: &[T],
-    ) -> Self {
-        let target = match kind {
-            BufferKind::Vertex => glow::ARRAY_BUFFER,
-            BufferKind::Index => glow::ELEMENT_ARRAY_BUFFER,
-        };
-        let usage = match usage {
-            BufferUsage::Static => glow::STATIC_DRAW,
-            BufferUsage::Dynamic => glow::DYNAMIC_DRAW,
-            BufferUsage::Stream => glow::STREAM_DRAW,
-        };
-
+    ) -> Result<Self, Error> {
         let size = mem::size_of_val(data);
+        if size == 0 {
+            return Err(Error::InvalidSize);
+        }
 
-        let inner = unsafe {
-            let buffer = ctx.inner.create_buffer().unwrap();
-            ctx.inner.bind_buffer(target, Some(buffer));
-            let data = slice::from_raw_parts(data.as_ptr() as *const u8, size);
-            ctx.inner.buffer_data_u8_slice(target, data, usage);
-            buffer
-        };
+        let data = unsafe { slice::from_raw_parts(data.as_ptr() as *const u8, size) };
+        let id = ctx.backend.create_buffer(kind, usage, size, Some(data))?;
 
-        Self {
-            inner,
+        Ok(Self {
+            id,
             kind,
             size,
-            ctx: ctx.inner.clone(),
-        }
+            usage,
+            backend: ctx.backend.clone(),
+        })
     }
 
     /// Updates the contents of the buffer with the given data.
+    ///
+    /// # Panics
+    /// Panics if `data` is larger than the buffer.
     pub fn update<T>(&self, data: &[T]) {
+        self.update_range(0, data);
+    }
+
+    /// Updates the contents of the buffer starting at `offset` bytes with the
+    /// given data, leaving the rest of the buffer untouched.
+    ///
+    /// # Panics
+    /// Panics if the updated range does not fit in the buffer.
+    pub fn update_range<T>(&self, offset: usize, data: &[T]) {
         let size = mem::size_of_val(data);
         assert!(
-            size <= self.size,
-            "Update data cannot be larger than the buffer"
+            offset
+                .checked_add(size)
+                .map_or(false, |end| end <= self.size),
+            "Update range is out of bounds"
         );
 
-        let target = match self.kind {
-            BufferKind::Vertex => glow::ARRAY_BUFFER,
-            BufferKind::Index => glow::ELEMENT_ARRAY_BUFFER,
-        };
+        let data = unsafe { slice::from_raw_parts(data.as_ptr() as *const u8, size) };
+        self.backend.update_buffer(self.id, offset, data);
+    }
 
-        unsafe {
-            self.ctx.bind_buffer(target, Some(self.inner));
-            let data = slice::from_raw_parts(data.as_ptr() as *const u8, size);
-            self.ctx.buffer_sub_data_u8_slice(target, 0, data);
-        }
+    /// Discards the contents of the buffer, leaving them undefined.
+    ///
+    /// Invalidating a buffer before rewriting it lets the backend hand out
+    /// fresh storage instead of waiting for the GPU to finish reading the old
+    /// contents. On OpenGL this orphans the buffer's storage.
+    ///
+    /// # Panics
+    /// Panics if the buffer was not created with [`BufferUsage::Stream`].
+    pub fn invalidate(&self) {
+        assert_eq!(
+            self.usage,
+            BufferUsage::Stream,
+            "Only stream buffers can be invalidated"
+        );
+        self.backend.invalidate_buffer(self.id);
     }
 
     /// Returns the size of the buffer in bytes.
     pub fn size(&self) -> usize {
         self.size
     }
+
+    /// Returns the id of this buffer in recorded [`Command`](crate::Command)s.
+    #[cfg(feature = "recording")]
+    pub fn id(&self) -> u32 {
+        self.id.0
+    }
 }
 
 impl Drop for Buffer {
     fn drop(&mut self) {
-        unsafe {
-            self.ctx.delete_buffer(self.inner);
-        }
+        self.backend.destroy_buffer(self.id);
     }
 }
@@ -4,26 +4,33 @@ use alloc::vec::Vec;
 use core::cell::RefCell;
 use core::{ffi, mem};
 
-use glow::{Framebuffer, HasContext};
-
+use crate::backend::{Backend, GlBackend};
+#[cfg(feature = "recording")]
+use crate::backend::{Command, RecordingBackend};
+#[cfg(feature = "software")]
+use crate::backend::{SoftwareBackend, SoftwareShader};
 use crate::{
-    gl_blend_factor, gl_blend_op, BlendState, Buffer, BufferKind, BufferLayout, BufferUsage, Image,
-    ImageFilter, ImageFormat, ImageUniform, ImageWrap, PassAction, Pipeline, PipelineInternal,
-    Shader, Uniform, UniformFormat, VertexAttribute,
+    Buffer, BufferKind, BufferLayout, BufferUsage, Error, Image, ImageFilter, ImageFormat,
+    ImageKind, ImageUniform, PassAction, Pipeline, PipelineState, Pipelines, RenderPass, Sampler,
+    SamplerState, Shader, StreamBuffer, Uniform, UniformBlock, UniformBlockBinding,
+    VertexAttribute,
 };
 
 pub(crate) struct ContextState {
-    pub pipelines: Vec<PipelineInternal>,
-    pub curr_pipeline: Option<usize>,
-    pub idx_buffer_set: bool,
+    pub pipelines: Pipelines,
+    pub curr_pipeline: Option<Pipeline>,
+    pub idx_buffer_offset: Option<usize>,
+    pub frame: u64,
 }
 
 /// A rendering context.
 pub struct Context {
-    pub(crate) inner: Rc<glow::Context>,
+    pub(crate) backend: Rc<dyn Backend>,
     pub(crate) state: Rc<RefCell<ContextState>>,
-    default_framebuffer: Framebuffer,
-    // TODO: should we cache GL state?
+    #[cfg(feature = "recording")]
+    recording: Option<Rc<RecordingBackend>>,
+    #[cfg(feature = "software")]
+    software: Option<Rc<SoftwareBackend>>,
 }
 
 impl Context {
@@ -32,102 +39,482 @@ impl Context {
     where
         F: FnMut(&str) -> *const ffi::c_void,
     {
-        let inner = Rc::new(unsafe { glow::Context::from_loader_function(loader_function) });
-        unsafe {
-            let vao = inner.create_vertex_array().unwrap();
-            inner.bind_vertex_array(Some(vao));
-        }
-
-        // TODO: fix this (blocked by grovesNL/glow#187)
-        let default_framebuffer =
-            unsafe { mem::transmute(inner.get_parameter_i32(glow::FRAMEBUFFER_BINDING) as u32) };
+        Self::with_backend(Rc::new(GlBackend::new(loader_function)))
+    }
 
+    pub(crate) fn with_backend(backend: Rc<dyn Backend>) -> Self {
         let state = Rc::new(RefCell::new(ContextState {
-            pipelines: Vec::new(),
+            pipelines: Pipelines::new(),
             curr_pipeline: None,
-            idx_buffer_set: false,
+            idx_buffer_offset: None,
+            frame: 0,
         }));
 
         Self {
-            inner,
-            default_framebuffer,
+            backend,
             state,
+            #[cfg(feature = "recording")]
+            recording: None,
+            #[cfg(feature = "software")]
+            software: None,
+        }
+    }
+
+    /// Creates a new context that records every call instead of rendering.
+    ///
+    /// All resource creation succeeds, and every shader is assumed to have
+    /// every attribute and uniform it is asked for. The recorded commands can
+    /// be inspected with [`Context::recorded_commands`].
+    ///
+    /// ```
+    /// use fugu::*;
+    ///
+    /// let ctx = Context::new_recording();
+    /// let buffer = ctx.create_buffer_with_data(
+    ///     BufferKind::Index,
+    ///     BufferUsage::Static,
+    ///     &[0u16, 1, 2, 0, 2, 3],
+    /// );
+    /// ctx.set_index_buffer(&buffer);
+    ///
+    /// assert_eq!(
+    ///     ctx.recorded_commands().last(),
+    ///     Some(&Command::SetIndexBuffer { buffer: buffer.id() }),
+    /// );
+    /// ```
+    #[cfg(feature = "recording")]
+    pub fn new_recording() -> Self {
+        let recording = Rc::new(RecordingBackend::new());
+        Self {
+            recording: Some(recording.clone()),
+            ..Self::with_backend(recording)
         }
     }
 
+    /// Returns the commands recorded so far.
+    ///
+    /// # Panics
+    /// Panics if the context was not created with [`Context::new_recording`].
+    #[cfg(feature = "recording")]
+    pub fn recorded_commands(&self) -> Vec<Command> {
+        self.recording().commands()
+    }
+
+    /// Clears the commands recorded so far.
+    ///
+    /// # Panics
+    /// Panics if the context was not created with [`Context::new_recording`].
+    #[cfg(feature = "recording")]
+    pub fn clear_recorded_commands(&self) {
+        self.recording().clear_commands();
+    }
+
+    /// Returns the number of resources that have been created but not yet
+    /// dropped.
+    ///
+    /// # Panics
+    /// Panics if the context was not created with [`Context::new_recording`].
+    #[cfg(feature = "recording")]
+    pub fn recorded_live_resources(&self) -> usize {
+        self.recording().live_resources()
+    }
+
+    #[cfg(feature = "recording")]
+    fn recording(&self) -> &RecordingBackend {
+        self.recording
+            .as_deref()
+            .expect("Context was not created with Context::new_recording")
+    }
+
+    /// Creates a new context that rasterizes on the CPU into a default
+    /// framebuffer of the given size.
+    ///
+    /// Rendering is deterministic, which makes it suitable for comparing
+    /// output against reference images. Shaders must be created with
+    /// [`Context::create_software_shader`]. Uniform buffers and color
+    /// attachments other than the first are ignored. The default
+    /// framebuffer has an [`ImageFormat::Rgba8`] color buffer and an
+    /// [`ImageFormat::Depth24Stencil8`] depth/stencil buffer.
+    ///
+    /// ```
+    /// use fugu::*;
+    ///
+    /// let ctx = Context::new_software(4, 4);
+    /// let shader = ctx.create_software_shader(
+    ///     SoftwareShader {
+    ///         attributes: &["pos"],
+    ///         vertex: Box::new(|input| VertexOutput {
+    ///             position: input.attributes[0],
+    ///             varyings: Vec::new(),
+    ///         }),
+    ///         fragment: Box::new(|_| Some([1.0, 0.0, 0.0, 1.0])),
+    ///     },
+    ///     &[],
+    ///     &[],
+    /// );
+    /// let pipeline = ctx.create_pipeline(
+    ///     &shader,
+    ///     &[BufferLayout::default()],
+    ///     &[VertexAttribute {
+    ///         name: "pos",
+    ///         format: VertexFormat::Float2,
+    ///         buffer_index: 0,
+    ///     }],
+    ///     PipelineState::default(),
+    /// );
+    /// let vertices = ctx.create_buffer_with_data(
+    ///     BufferKind::Vertex,
+    ///     BufferUsage::Static,
+    ///     &[-1.0f32, -1.0, 3.0, -1.0, -1.0, 3.0],
+    /// );
+    ///
+    /// ctx.begin_default_pass(PassAction::Clear {
+    ///     color: Some((0.0, 0.0, 0.0, 1.0)),
+    ///     depth: None,
+    ///     stencil: None,
+    /// });
+    /// ctx.set_pipeline(&pipeline);
+    /// ctx.set_vertex_buffer(&vertices);
+    /// ctx.draw(0, 3, 1);
+    /// ctx.end_render_pass();
+    ///
+    /// let mut pixel = [0; 4];
+    /// ctx.read_pixels(0, 0, 1, 1, &mut pixel);
+    /// assert_eq!(pixel, [255, 0, 0, 255]);
+    /// ```
+    #[cfg(feature = "software")]
+    pub fn new_software(width: u32, height: u32) -> Self {
+        let software = Rc::new(SoftwareBackend::new(width, height));
+        Self {
+            software: Some(software.clone()),
+            ..Self::with_backend(software)
+        }
+    }
+
+    /// Creates and returns a new [`Shader`] running the given closures.
+    ///
+    /// # Panics
+    /// Panics if the context was not created with [`Context::new_software`],
+    /// or if the shader could not be created. See
+    /// [`Context::try_create_software_shader`] for a fallible version.
+    #[cfg(feature = "software")]
+    pub fn create_software_shader(
+        &self,
+        shader: SoftwareShader,
+        uniforms: &[Uniform],
+        images: &[ImageUniform],
+    ) -> Shader {
+        self.try_create_software_shader(shader, uniforms, images)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Creates and returns a new [`Shader`] running the given closures, or an
+    /// [`Error`] if the shader could not be created.
+    ///
+    /// # Panics
+    /// Panics if the context was not created with [`Context::new_software`].
+    #[cfg(feature = "software")]
+    pub fn try_create_software_shader(
+        &self,
+        shader: SoftwareShader,
+        uniforms: &[Uniform],
+        images: &[ImageUniform],
+    ) -> Result<Shader, Error> {
+        Shader::new_software(self, self.software(), shader, uniforms, images)
+    }
+
+    #[cfg(feature = "software")]
+    fn software(&self) -> &SoftwareBackend {
+        self.software
+            .as_deref()
+            .expect("Context was not created with Context::new_software")
+    }
+
     /// Creates and returns a new [`Buffer`].
+    ///
+    /// # Panics
+    /// Panics if the buffer could not be created. See
+    /// [`Context::try_create_buffer`] for a fallible version.
     pub fn create_buffer(&self, kind: BufferKind, usage: BufferUsage, size: usize) -> Buffer {
+        self.try_create_buffer(kind, usage, size)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Creates and returns a new [`Buffer`], or an [`Error`] if the buffer
+    /// could not be created.
+    pub fn try_create_buffer(
+        &self,
+        kind: BufferKind,
+        usage: BufferUsage,
+        size: usize,
+    ) -> Result<Buffer, Error> {
         Buffer::new(self, kind, usage, size)
     }
 
     /// Creates and returns a new [`Buffer`] with the given data.
+    ///
+    /// # Panics
+    /// Panics if the buffer could not be created. See
+    /// [`Context::try_create_buffer_with_data`] for a fallible version.
     pub fn create_buffer_with_data<T>(
         &self,
         kind: BufferKind,
         usage: BufferUsage,
         data: &[T],
     ) -> Buffer {
+        self.try_create_buffer_with_data(kind, usage, data)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Creates and returns a new [`Buffer`] with the given data, or an
+    /// [`Error`] if the buffer could not be created.
+    pub fn try_create_buffer_with_data<T>(
+        &self,
+        kind: BufferKind,
+        usage: BufferUsage,
+        data: &[T],
+    ) -> Result<Buffer, Error> {
         Buffer::with_data(self, kind, usage, data)
     }
 
-    /// Creates and returns a new [`Image`].
+    /// Creates and returns a new [`StreamBuffer`] handing out up to `size`
+    /// bytes per frame, cycling through `frame_count` buffers.
+    ///
+    /// # Panics
+    /// Panics if the buffer could not be created. See
+    /// [`Context::try_create_stream_buffer`] for a fallible version.
+    pub fn create_stream_buffer(
+        &self,
+        kind: BufferKind,
+        size: usize,
+        frame_count: usize,
+    ) -> StreamBuffer {
+        self.try_create_stream_buffer(kind, size, frame_count)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Creates and returns a new [`StreamBuffer`] handing out up to `size`
+    /// bytes per frame, cycling through `frame_count` buffers, or an
+    /// [`Error`] if the buffer could not be created.
+    pub fn try_create_stream_buffer(
+        &self,
+        kind: BufferKind,
+        size: usize,
+        frame_count: usize,
+    ) -> Result<StreamBuffer, Error> {
+        StreamBuffer::new(self, kind, size, frame_count)
+    }
+
+    /// Creates and returns a new [`Image`] with `mip_count` mip levels.
+    ///
+    /// # Panics
+    /// Panics if the image could not be created. See
+    /// [`Context::try_create_image`] for a fallible version.
     pub fn create_image(
         &self,
+        kind: ImageKind,
         width: u32,
         height: u32,
         format: ImageFormat,
-        filter: ImageFilter,
-        wrap: ImageWrap,
+        mip_count: u32,
     ) -> Image {
-        Image::new(self, width, height, format, filter, wrap)
+        self.try_create_image(kind, width, height, format, mip_count)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Creates and returns a new [`Image`] with `mip_count` mip levels, or an
+    /// [`Error`] if the image could not be created.
+    pub fn try_create_image(
+        &self,
+        kind: ImageKind,
+        width: u32,
+        height: u32,
+        format: ImageFormat,
+        mip_count: u32,
+    ) -> Result<Image, Error> {
+        Image::new(self, kind, width, height, format, mip_count)
     }
 
-    /// Creates and returns a new [`Image`] with the given data.
+    /// Creates and returns a new [`Image`] with `mip_count` mip levels and the
+    /// given data for the first level.
+    ///
+    /// The data holds every layer of the first level one after another, e.g.
+    /// the faces of a cube map or the slices of a 3D image.
+    ///
+    /// # Panics
+    /// Panics if the image could not be created. See
+    /// [`Context::try_create_image_with_data`] for a fallible version.
     pub fn create_image_with_data(
         &self,
+        kind: ImageKind,
         width: u32,
         height: u32,
         format: ImageFormat,
-        filter: ImageFilter,
-        wrap: ImageWrap,
+        mip_count: u32,
         data: &[u8],
     ) -> Image {
-        Image::with_data(self, width, height, format, filter, wrap, data)
+        self.try_create_image_with_data(kind, width, height, format, mip_count, data)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Creates and returns a new [`Image`] with `mip_count` mip levels and the
+    /// given data for the first level, or an [`Error`] if the image could not
+    /// be created.
+    pub fn try_create_image_with_data(
+        &self,
+        kind: ImageKind,
+        width: u32,
+        height: u32,
+        format: ImageFormat,
+        mip_count: u32,
+        data: &[u8],
+    ) -> Result<Image, Error> {
+        Image::with_data(self, kind, width, height, format, mip_count, data)
+    }
+
+    /// Creates and returns a new [`Sampler`] with the given state.
+    ///
+    /// # Panics
+    /// Panics if the sampler could not be created. See
+    /// [`Context::try_create_sampler`] for a fallible version.
+    pub fn create_sampler(&self, state: SamplerState) -> Sampler {
+        self.try_create_sampler(state)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Creates and returns a new [`Sampler`] with the given state, or an
+    /// [`Error`] if the sampler could not be created.
+    pub fn try_create_sampler(&self, state: SamplerState) -> Result<Sampler, Error> {
+        Sampler::new(self, state)
+    }
+
+    /// Creates and returns a new [`RenderPass`] rendering into the given
+    /// color images and optional depth/stencil image.
+    ///
+    /// # Panics
+    /// Panics if the render pass could not be created. See
+    /// [`Context::try_create_render_pass`] for a fallible version.
+    pub fn create_render_pass(
+        &self,
+        color: &[&Image],
+        depth_stencil: Option<&Image>,
+    ) -> RenderPass {
+        self.try_create_render_pass(color, depth_stencil)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Creates and returns a new [`RenderPass`] rendering into the given
+    /// color images and optional depth/stencil image, or an [`Error`] if the
+    /// render pass could not be created.
+    pub fn try_create_render_pass(
+        &self,
+        color: &[&Image],
+        depth_stencil: Option<&Image>,
+    ) -> Result<RenderPass, Error> {
+        RenderPass::new(self, color, depth_stencil)
     }
 
-    /// Creates and returns a new [`Pipeline`].
+    /// Creates and returns a new [`Pipeline`] using `shader`.
+    ///
+    /// A shader can be shared by any number of pipelines, e.g. with different
+    /// vertex layouts or blend states; attribute locations are looked up for
+    /// each pipeline.
+    ///
+    /// # Panics
+    /// Panics if the pipeline could not be created. See
+    /// [`Context::try_create_pipeline`] for a fallible version.
     pub fn create_pipeline(
         &self,
-        shader: Shader,
+        shader: &Shader,
         buffers: &[BufferLayout],
         attrs: &[VertexAttribute],
+        state: PipelineState,
     ) -> Pipeline {
-        Pipeline::new(self, shader, buffers, attrs)
+        self.try_create_pipeline(shader, buffers, attrs, state)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Creates and returns a new [`Pipeline`] using `shader`, or an [`Error`]
+    /// if the pipeline could not be created.
+    pub fn try_create_pipeline(
+        &self,
+        shader: &Shader,
+        buffers: &[BufferLayout],
+        attrs: &[VertexAttribute],
+        state: PipelineState,
+    ) -> Result<Pipeline, Error> {
+        Pipeline::new(self, shader, buffers, attrs, state)
+    }
+
+    /// Destroys a [`Pipeline`], releasing its reference to its [`Shader`]. If
+    /// the pipeline is the current pipeline, no pipeline is set afterwards.
+    ///
+    /// # Panics
+    /// Panics if the pipeline was already destroyed.
+    pub fn destroy_pipeline(&self, pipeline: Pipeline) {
+        let mut state = self.state.borrow_mut();
+        let internal = state.pipelines.remove(pipeline);
+        if state.curr_pipeline == Some(pipeline) {
+            state.curr_pipeline = None;
+        }
+        // Dropping the shader calls into the backend, so release the state
+        // first.
+        drop(state);
+        drop(internal);
     }
 
     /// Creates and returns a new [`Shader`] with the given shader source.
     ///
     /// Shader translation based on the rendering backend must be handled by the
     /// caller.
+    ///
+    /// # Panics
+    /// Panics if the shader could not be created. See
+    /// [`Context::try_create_shader`] for a fallible version.
     pub fn create_shader(
         &self,
         vert_source: impl AsRef<[u8]>,
         frag_source: impl AsRef<[u8]>,
         uniforms: &[Uniform],
         images: &[ImageUniform],
+        uniform_blocks: &[UniformBlockBinding],
     ) -> Shader {
-        Shader::new(self, vert_source, frag_source, uniforms, images)
+        self.try_create_shader(vert_source, frag_source, uniforms, images, uniform_blocks)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Creates and returns a new [`Shader`] with the given shader source, or
+    /// an [`Error`] if the shader could not be compiled or linked.
+    ///
+    /// Shader translation based on the rendering backend must be handled by the
+    /// caller.
+    pub fn try_create_shader(
+        &self,
+        vert_source: impl AsRef<[u8]>,
+        frag_source: impl AsRef<[u8]>,
+        uniforms: &[Uniform],
+        images: &[ImageUniform],
+        uniform_blocks: &[UniformBlockBinding],
+    ) -> Result<Shader, Error> {
+        Shader::new(
+            self,
+            vert_source,
+            frag_source,
+            uniforms,
+            images,
+            uniform_blocks,
+        )
     }
 
     /// Sets the current pipeline.
+    ///
+    /// # Panics
+    /// Panics if the pipeline was destroyed.
     pub fn set_pipeline(&self, pipeline: &Pipeline) {
-        self.state.borrow_mut().curr_pipeline = Some(pipeline.id);
-        unsafe {
-            self.inner.use_program(Some(
-                self.state.borrow().pipelines[pipeline.id].shader.inner,
-            ));
-        }
-        // TODO: other stuff
+        let mut state = self.state.borrow_mut();
+        let internal = state.pipelines.get(*pipeline);
+        self.backend.apply_pipeline(*pipeline, internal);
+        state.curr_pipeline = Some(*pipeline);
     }
 
     /// Sets the current vertex buffer.
@@ -137,224 +524,257 @@ impl Context {
 
     /// Sets the current vertex buffers.
     pub fn set_vertex_buffers(&self, buffers: &[&Buffer]) {
-        let pipeline = &self.state.borrow().pipelines[self.state.borrow().curr_pipeline.unwrap()];
-        for (buffer_index, attrs) in pipeline.attrs.iter().enumerate() {
-            unsafe {
-                self.inner
-                    .bind_buffer(glow::ARRAY_BUFFER, Some(buffers[buffer_index].inner));
-                for attr in attrs {
-                    self.inner.enable_vertex_attrib_array(attr.location);
-                    self.inner.vertex_attrib_pointer_f32(
-                        attr.location,
-                        attr.size,
-                        attr.format,
-                        false,
-                        attr.stride,
-                        attr.offset,
-                    );
-                    self.inner
-                        .vertex_attrib_divisor(attr.location, attr.divisor);
-                }
-            }
+        let buffers = buffers
+            .iter()
+            .map(|buffer| (*buffer, 0))
+            .collect::<Vec<_>>();
+        self.set_vertex_buffers_with_offsets(&buffers);
+    }
+
+    /// Sets the current vertex buffers, each starting at the given offset in
+    /// bytes, e.g. as returned by [`StreamBuffer::push`].
+    ///
+    /// # Panics
+    /// Panics if an offset is out of bounds.
+    pub fn set_vertex_buffers_with_offsets(&self, buffers: &[(&Buffer, usize)]) {
+        for (buffer, offset) in buffers {
+            assert!(
+                *offset <= buffer.size,
+                "Vertex buffer offset is out of bounds"
+            );
         }
+
+        let state = self.state.borrow();
+        let pipeline = state.pipelines.get(state.curr_pipeline.unwrap());
+        let ids = buffers
+            .iter()
+            .map(|(buffer, offset)| (buffer.id, *offset))
+            .collect::<Vec<_>>();
+        self.backend.apply_vertex_buffers(&pipeline.attrs, &ids);
     }
 
     /// Sets the current index buffer.
     pub fn set_index_buffer(&self, buffer: &Buffer) {
-        unsafe {
-            self.inner
-                .bind_buffer(glow::ELEMENT_ARRAY_BUFFER, Some(buffer.inner));
-        }
-        self.state.borrow_mut().idx_buffer_set = true;
+        self.set_index_buffer_with_offset(buffer, 0);
+    }
+
+    /// Sets the current index buffer, starting at the given offset in bytes,
+    /// e.g. as returned by [`StreamBuffer::push`].
+    ///
+    /// The offset must be a multiple of the index size of the pipeline used
+    /// to draw.
+    ///
+    /// # Panics
+    /// Panics if `offset` is out of bounds.
+    pub fn set_index_buffer_with_offset(&self, buffer: &Buffer, offset: usize) {
+        assert!(
+            offset <= buffer.size,
+            "Index buffer offset is out of bounds"
+        );
+
+        self.backend.apply_index_buffer(buffer.id);
+        self.state.borrow_mut().idx_buffer_offset = Some(offset);
     }
 
     /// Sets shader uniforms.
-    pub fn set_uniforms<T>(&self, data: T) {
-        let pipeline = &self.state.borrow().pipelines[self.state.borrow().curr_pipeline.unwrap()];
-        let shader = &pipeline.shader;
-
-        let mut ptr = &data as *const T as *const ffi::c_void;
-        for uniform in &shader.uniforms {
-            match uniform.format {
-                UniformFormat::Float1 => unsafe {
-                    self.inner.uniform_1_f32_slice(
-                        Some(&uniform.location),
-                        slice::from_raw_parts(ptr.cast(), 1),
-                    );
-                    ptr = ptr.add(mem::size_of::<f32>());
-                },
-                UniformFormat::Float2 => unsafe {
-                    self.inner.uniform_2_f32_slice(
-                        Some(&uniform.location),
-                        slice::from_raw_parts(ptr.cast(), 2),
-                    );
-                    ptr = ptr.add(mem::size_of::<[f32; 2]>());
-                },
-                UniformFormat::Float3 => unsafe {
-                    self.inner.uniform_3_f32_slice(
-                        Some(&uniform.location),
-                        slice::from_raw_parts(ptr.cast(), 3),
-                    );
-                    ptr = ptr.add(mem::size_of::<[f32; 3]>());
-                },
-                UniformFormat::Float4 => unsafe {
-                    self.inner.uniform_4_f32_slice(
-                        Some(&uniform.location),
-                        slice::from_raw_parts(ptr.cast(), 4),
-                    );
-                    ptr = ptr.add(mem::size_of::<[f32; 4]>());
-                },
-                UniformFormat::Int1 => unsafe {
-                    self.inner.uniform_1_i32_slice(
-                        Some(&uniform.location),
-                        slice::from_raw_parts(ptr.cast(), 1),
-                    );
-                    ptr = ptr.add(mem::size_of::<i32>());
-                },
-                UniformFormat::Int2 => unsafe {
-                    self.inner.uniform_2_i32_slice(
-                        Some(&uniform.location),
-                        slice::from_raw_parts(ptr.cast(), 2),
-                    );
-                    ptr = ptr.add(mem::size_of::<[i32; 2]>());
-                },
-                UniformFormat::Int3 => unsafe {
-                    self.inner.uniform_3_i32_slice(
-                        Some(&uniform.location),
-                        slice::from_raw_parts(ptr.cast(), 3),
-                    );
-                    ptr = ptr.add(mem::size_of::<[i32; 3]>());
-                },
-                UniformFormat::Int4 => unsafe {
-                    self.inner.uniform_4_i32_slice(
-                        Some(&uniform.location),
-                        slice::from_raw_parts(ptr.cast(), 4),
-                    );
-                    ptr = ptr.add(mem::size_of::<[i32; 4]>());
-                },
-            }
+    ///
+    /// `data` must follow the packed layout described in [`Uniform`].
+    ///
+    /// The layout of `T` is checked here rather than in
+    /// [`Context::set_pipeline`], as the uniform type is only known once data
+    /// is passed. The check compares constant layouts, so it is cheap.
+    ///
+    /// # Panics
+    /// Panics if the layout of `T` does not match the current shader's
+    /// uniforms.
+    pub fn set_uniforms<T: UniformBlock>(&self, data: T) {
+        let state = self.state.borrow();
+        let pipeline = state.pipelines.get(state.curr_pipeline.unwrap());
+        let shader = &pipeline.shader.internal;
+
+        let layout_matches = T::LAYOUT.len() == shader.uniforms.len()
+            && T::LAYOUT
+                .iter()
+                .zip(&shader.uniforms)
+                .all(|(&(format, count), uniform)| {
+                    format == uniform.format && count == uniform.count
+                });
+        if !layout_matches {
+            panic!(
+                "Uniform data layout {:?} does not match the shader's uniforms {:?}",
+                T::LAYOUT,
+                shader
+                    .uniforms
+                    .iter()
+                    .map(|uniform| (uniform.format, uniform.count))
+                    .collect::<Vec<_>>()
+            );
         }
+        assert_eq!(
+            mem::size_of::<T>(),
+            shader.uniforms_size,
+            "Uniform data size does not match the shader's uniforms"
+        );
+        assert!(
+            mem::size_of::<T>() == 0 || mem::align_of::<T>() >= mem::align_of::<f32>(),
+            "Uniform data must be aligned to 4 bytes"
+        );
+
+        let data =
+            unsafe { slice::from_raw_parts(&data as *const T as *const u8, mem::size_of::<T>()) };
+        self.backend.apply_uniforms(shader.id, data);
     }
 
-    /// Sets shader images.
-    pub fn set_images(&self, images: &[&Image]) {
-        let pipeline = &self.state.borrow().pipelines[self.state.borrow().curr_pipeline.unwrap()];
-        let shader = &pipeline.shader;
+    /// Binds a range of a uniform buffer to the given uniform block binding
+    /// index.
+    ///
+    /// # Panics
+    /// Panics if `buffer` is not a uniform buffer, if the range is out of
+    /// bounds, or if `offset` is not a multiple of
+    /// [`Context::uniform_buffer_offset_alignment`].
+    pub fn set_uniform_buffer(&self, binding: u32, buffer: &Buffer, offset: usize, size: usize) {
+        assert_eq!(
+            buffer.kind,
+            BufferKind::Uniform,
+            "Only uniform buffers can be bound to uniform blocks"
+        );
+        assert!(
+            offset
+                .checked_add(size)
+                .map_or(false, |end| end <= buffer.size),
+            "Uniform buffer range is out of bounds"
+        );
+        assert_eq!(
+            offset % self.backend.uniform_buffer_offset_alignment(),
+            0,
+            "Uniform buffer offset is not properly aligned"
+        );
 
-        for (i, image_uniform) in shader.image_uniforms.iter().enumerate() {
-            unsafe {
-                self.inner.active_texture(glow::TEXTURE0 + i as u32);
-                self.inner
-                    .bind_texture(glow::TEXTURE_2D, Some(images[i].inner));
-                self.inner
-                    .uniform_1_i32(Some(&image_uniform.location), i as i32);
-            }
+        self.backend
+            .apply_uniform_buffer(binding, buffer.id, offset, size);
+    }
+
+    /// Returns the required alignment in bytes of offsets passed to
+    /// [`Context::set_uniform_buffer`].
+    pub fn uniform_buffer_offset_alignment(&self) -> usize {
+        self.backend.uniform_buffer_offset_alignment()
+    }
+
+    /// Sets shader images, each sampled with the given [`Sampler`].
+    ///
+    /// # Panics
+    /// Panics if an integer image is sampled with linear filtering, or if a
+    /// comparison sampler is used with an image without a depth format.
+    pub fn set_images(&self, images: &[(&Image, &Sampler)]) {
+        let state = self.state.borrow();
+        let pipeline = state.pipelines.get(state.curr_pipeline.unwrap());
+        let shader = &pipeline.shader.internal;
+
+        let images = &images[..shader.image_count];
+        for (image, sampler) in images {
+            let state = sampler.state();
+            assert!(
+                !image.format().is_integer()
+                    || (state.min_filter == ImageFilter::Nearest
+                        && state.mag_filter == ImageFilter::Nearest
+                        && state.mipmap_filter != Some(ImageFilter::Linear)),
+                "Integer images must be sampled with nearest filtering"
+            );
+            assert!(
+                state.compare.is_none() || image.format().is_depth(),
+                "Comparison samplers can only sample depth images"
+            );
         }
+
+        let ids = images
+            .iter()
+            .map(|(image, sampler)| (image.internal.id, sampler.id))
+            .collect::<Vec<_>>();
+        self.backend.apply_images(shader.id, &ids);
     }
 
     /// Draws geometry from the current vertex and index buffers.
     ///
     /// # Arguments
-    /// * `start` - The index of the first vertex to draw.
+    /// * `start` - The index of the first vertex, or of the first index if an
+    ///   index buffer is set, to draw.
     /// * `count` - The number of vertices to draw.
     /// * `instances` - The number of instances to draw.
+    ///
+    /// # Panics
+    /// Panics if the offset of the current index buffer is not a multiple of
+    /// the pipeline's index size.
     pub fn draw(&self, start: usize, count: usize, instances: usize) {
-        unsafe {
-            if self.state.borrow().idx_buffer_set {
-                self.inner.draw_elements_instanced(
-                    glow::TRIANGLES,
-                    count as _,
-                    glow::UNSIGNED_SHORT,
-                    start as i32 * 2,
-                    instances as _,
-                );
-            } else {
-                self.inner.draw_arrays_instanced(
-                    glow::TRIANGLES,
-                    start as _,
-                    count as _,
-                    instances as _,
+        let state = self.state.borrow();
+        let pipeline = state.pipelines.get(state.curr_pipeline.unwrap());
+        let (index_type, start) = match state.idx_buffer_offset {
+            Some(offset) => {
+                let index_size = pipeline.index_type.size();
+                assert_eq!(
+                    offset % index_size,
+                    0,
+                    "Index buffer offset is not a multiple of the index size"
                 );
+                (Some(pipeline.index_type), start + offset / index_size)
             }
-        }
+            None => (None, start),
+        };
+        self.backend
+            .draw(pipeline.primitive, index_type, start, count, instances);
     }
 
     /// Begins the default render pass with the given [`PassAction`].
     pub fn begin_default_pass(&self, action: PassAction) {
-        match action {
-            PassAction::Nothing => {}
-            PassAction::Clear {
-                color,
-                depth,
-                stencil,
-            } => unsafe {
-                let mut clear_flag = 0;
-                if let Some((r, g, b, a)) = color {
-                    self.inner.clear_color(r, g, b, a);
-                    clear_flag |= glow::COLOR_BUFFER_BIT;
-                }
-                if let Some(depth) = depth {
-                    self.inner.clear_depth_f32(depth);
-                    clear_flag |= glow::DEPTH_BUFFER_BIT;
-                }
-                if let Some(stencil) = stencil {
-                    self.inner.clear_stencil(stencil);
-                    clear_flag |= glow::STENCIL_BUFFER_BIT;
-                }
-                self.inner.clear(clear_flag);
-            },
-        }
+        self.backend.begin_pass(None, action);
+    }
+
+    /// Begins an offscreen render pass with the given [`PassAction`].
+    ///
+    /// The viewport is set to cover the whole render pass, and is restored
+    /// when the pass is ended with [`Context::end_render_pass`].
+    pub fn begin_pass(&self, pass: &RenderPass, action: PassAction) {
+        self.backend.begin_pass(Some(pass.id), action);
     }
 
     /// Ends the current render pass.
     pub fn end_render_pass(&self) {
-        unsafe {
-            self.inner
-                .bind_framebuffer(glow::FRAMEBUFFER, Some(self.default_framebuffer));
-        }
+        self.backend.end_pass();
     }
 
     /// Commits everything that was drawn and performs cleanup.
+    ///
+    /// This also moves every [`StreamBuffer`] on to its next frame.
     pub fn commit_frame(&self) {
-        unsafe {
-            self.inner.bind_buffer(glow::ARRAY_BUFFER, None);
-            self.inner.bind_buffer(glow::ELEMENT_ARRAY_BUFFER, None);
-            // TODO: clear texture bindings?
-        }
+        self.backend.commit_frame();
 
-        self.state.borrow_mut().curr_pipeline = None;
-        self.state.borrow_mut().idx_buffer_set = false;
+        let mut state = self.state.borrow_mut();
+        state.curr_pipeline = None;
+        state.idx_buffer_offset = None;
+        state.frame = state.frame.wrapping_add(1);
     }
 
-    /// Sets the viewport.
-    pub fn set_viewport(&self, x: u32, y: u32, width: u32, height: u32) {
-        unsafe {
-            self.inner.viewport(x as _, y as _, width as _, height as _);
-        }
-    }
+    /// Reads pixels of the current render target into `buf` in
+    /// [`ImageFormat::Rgba8`], with the bottom row first. Outside of a render
+    /// pass, the default framebuffer is read.
+    ///
+    /// The contents of pixels outside of the render target are undefined.
+    ///
+    /// # Panics
+    /// Panics if the size of `buf` does not match the size of the region.
+    pub fn read_pixels(&self, x: u32, y: u32, width: u32, height: u32, buf: &mut [u8]) {
+        assert_eq!(
+            buf.len(),
+            width as usize * height as usize * ImageFormat::Rgba8.size(),
+            "Buffer size does not match the pixel region"
+        );
 
-    /// Sets the current blend state.
-    pub fn set_blend(&self, state: BlendState) {
-        unsafe {
-            self.inner.enable(glow::BLEND);
-            self.inner.blend_equation(gl_blend_op(state.op));
-            self.inner
-                .blend_func(gl_blend_factor(state.source), gl_blend_factor(state.dest));
-        }
+        self.backend
+            .read_pixels(x as _, y as _, width as _, height as _, buf);
     }
 
-    /// Sets the current blend state separately for color and alpha.
-    pub fn set_blend_separate(&self, color: BlendState, alpha: BlendState) {
-        unsafe {
-            self.inner.enable(glow::BLEND);
-            self.inner
-                .blend_equation_separate(gl_blend_op(color.op), gl_blend_op(alpha.op));
-            self.inner.blend_func_separate(
-                gl_blend_factor(color.source),
-                gl_blend_factor(color.dest),
-                gl_blend_factor(alpha.source),
-                gl_blend_factor(alpha.dest),
-            );
-        }
+    /// Sets the viewport.
+    pub fn set_viewport(&self, x: u32, y: u32, width: u32, height: u32) {
+        self.backend
+            .set_viewport(x as _, y as _, width as _, height as _);
     }
 }
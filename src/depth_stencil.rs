@@ -0,0 +1,97 @@
+/// A comparison function.
+#[allow(missing_docs)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareFunc {
+    Never,
+    Less,
+    Equal,
+    LessEqual,
+    Greater,
+    NotEqual,
+    GreaterEqual,
+    Always,
+}
+
+impl Default for CompareFunc {
+    fn default() -> Self {
+        CompareFunc::Always
+    }
+}
+
+/// A stencil operation.
+#[allow(missing_docs)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StencilOp {
+    Keep,
+    Zero,
+    Replace,
+    IncrementClamp,
+    DecrementClamp,
+    Invert,
+    IncrementWrap,
+    DecrementWrap,
+}
+
+impl Default for StencilOp {
+    fn default() -> Self {
+        StencilOp::Keep
+    }
+}
+
+/// Depth test state.
+///
+/// Depth testing is disabled if `compare` is [`CompareFunc::Always`] and
+/// `write` is `false`, which is the default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DepthState {
+    /// The depth comparison function.
+    pub compare: CompareFunc,
+    /// Whether depth values are written to the depth buffer.
+    pub write: bool,
+}
+
+impl DepthState {
+    pub(crate) fn enabled(&self) -> bool {
+        self.compare != CompareFunc::Always || self.write
+    }
+}
+
+/// Stencil test state for one face.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct StencilFaceState {
+    /// The stencil comparison function.
+    pub compare: CompareFunc,
+    /// The operation when the stencil test fails.
+    pub fail_op: StencilOp,
+    /// The operation when the stencil test passes but the depth test fails.
+    pub depth_fail_op: StencilOp,
+    /// The operation when both the stencil and the depth test pass.
+    pub pass_op: StencilOp,
+}
+
+/// Stencil test state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StencilState {
+    /// The stencil state for front-facing primitives.
+    pub front: StencilFaceState,
+    /// The stencil state for back-facing primitives.
+    pub back: StencilFaceState,
+    /// The mask applied to the reference and stored values when testing.
+    pub read_mask: u8,
+    /// The mask applied to stencil values when writing.
+    pub write_mask: u8,
+    /// The reference value.
+    pub reference: u8,
+}
+
+impl Default for StencilState {
+    fn default() -> Self {
+        Self {
+            front: StencilFaceState::default(),
+            back: StencilFaceState::default(),
+            read_mask: 0xff,
+            write_mask: 0xff,
+            reference: 0,
+        }
+    }
+}
@@ -0,0 +1,110 @@
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::{ShaderDiagnostic, ShaderStage};
+
+/// Errors that can occur when creating resources.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// A shader stage failed to compile.
+    ShaderCompile {
+        /// The stage that failed to compile.
+        stage: ShaderStage,
+        /// The diagnostics reported by the driver.
+        diagnostics: Vec<ShaderDiagnostic>,
+    },
+    /// A shader program failed to link.
+    ShaderLink {
+        /// The diagnostics reported by the driver.
+        diagnostics: Vec<ShaderDiagnostic>,
+    },
+    /// A uniform could not be found in the shader program.
+    MissingUniform {
+        /// The name of the uniform.
+        name: &'static str,
+    },
+    /// A uniform block could not be found in the shader program.
+    MissingUniformBlock {
+        /// The name of the uniform block.
+        name: &'static str,
+    },
+    /// A vertex attribute could not be found in the shader program.
+    MissingAttribute {
+        /// The name of the attribute.
+        name: &'static str,
+    },
+    /// A vertex attribute refers to a buffer layout that does not exist.
+    InvalidBufferIndex {
+        /// The out of range buffer index.
+        index: usize,
+    },
+    /// The backend failed to allocate the resource.
+    OutOfMemory,
+    /// The requested size or the size of the given data is invalid.
+    InvalidSize,
+    /// A [`BufferUsage::Static`](crate::BufferUsage::Static) buffer was
+    /// created without data.
+    MissingData,
+    /// A render pass was created without any attachments.
+    NoAttachments,
+    /// A render pass attachment is not a 2D image, or its format does not
+    /// match its use as a color or depth/stencil attachment.
+    InvalidAttachment,
+    /// A sampler's max anisotropy is zero, or its min LOD is greater than its
+    /// max LOD.
+    InvalidSamplerState,
+    /// The operation is not supported by the backend, e.g. creating a GLSL
+    /// shader on a context created with
+    /// [`Context::new_software`](crate::Context::new_software).
+    Unsupported,
+    /// A render pass framebuffer is incomplete.
+    IncompleteFramebuffer {
+        /// The framebuffer status reported by the driver.
+        status: u32,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::ShaderCompile { stage, diagnostics } => {
+                write!(f, "failed to compile {} shader", stage)?;
+                for diagnostic in diagnostics {
+                    write!(f, "\n  {}", diagnostic)?;
+                }
+                Ok(())
+            }
+            Error::ShaderLink { diagnostics } => {
+                f.write_str("failed to link shader program")?;
+                for diagnostic in diagnostics {
+                    write!(f, "\n  {}", diagnostic)?;
+                }
+                Ok(())
+            }
+            Error::MissingUniform { name } => write!(f, "uniform `{}` not found in shader", name),
+            Error::MissingUniformBlock { name } => {
+                write!(f, "uniform block `{}` not found in shader", name)
+            }
+            Error::MissingAttribute { name } => {
+                write!(f, "vertex attribute `{}` not found in shader", name)
+            }
+            Error::InvalidBufferIndex { index } => {
+                write!(f, "vertex attribute buffer index {} is out of range", index)
+            }
+            Error::OutOfMemory => f.write_str("out of memory"),
+            Error::InvalidSize => f.write_str("invalid size"),
+            Error::MissingData => f.write_str("static buffers must be initialized with data"),
+            Error::NoAttachments => f.write_str("render passes must have at least one attachment"),
+            Error::InvalidAttachment => f.write_str("invalid render pass attachment"),
+            Error::InvalidSamplerState => f.write_str("invalid sampler state"),
+            Error::Unsupported => f.write_str("operation not supported by the backend"),
+            Error::IncompleteFramebuffer { status } => {
+                write!(
+                    f,
+                    "render pass framebuffer is incomplete (status {:#x})",
+                    status
+                )
+            }
+        }
+    }
+}
@@ -1,28 +1,100 @@
 use alloc::rc::Rc;
 
-use glow::HasContext;
-
-use crate::Context;
+use crate::backend::{Backend, ImageDesc, ImageId};
+use crate::{Context, Error};
 
 /// A GPU image.
+///
+/// An image is destroyed once it and every [`RenderPass`](crate::RenderPass)
+/// rendering into it are dropped.
 pub struct Image {
-    pub(crate) inner: glow::Texture,
-    width: u32,
-    height: u32,
-    format: u32,
-    kind: u32,
-    ctx: Rc<glow::Context>,
+    pub(crate) internal: Rc<ImageInternal>,
+    desc: ImageDesc,
+}
+
+pub(crate) struct ImageInternal {
+    pub(crate) id: ImageId,
+    backend: Rc<dyn Backend>,
+}
+
+/// Kinds of GPU images.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageKind {
+    /// A 2D image.
+    D2,
+    /// A cube map of six square faces, stored as layers in the order +X, -X,
+    /// +Y, -Y, +Z, -Z.
+    Cube,
+    /// An array of 2D images with the same size and format.
+    Array {
+        /// The number of layers.
+        layers: u32,
+    },
+    /// A 3D image, stored as layers of 2D slices from front to back.
+    D3 {
+        /// The depth of the image in pixels.
+        depth: u32,
+    },
+}
+
+impl Default for ImageKind {
+    fn default() -> Self {
+        ImageKind::D2
+    }
 }
 
 /// Formats of a GPU image.
-#[allow(missing_docs)]
+///
+/// Multi-byte components are stored in native byte order. Integer formats
+/// must be sampled with [`ImageFilter::Nearest`].
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ImageFormat {
+    /// One 8-bit normalized component.
+    R8,
+    /// Two 8-bit normalized components.
+    Rg8,
+    /// Three 8-bit normalized components.
     Rgb8,
+    /// Four 8-bit normalized components.
     Rgba8,
+    /// Three 8-bit sRGB-encoded components, converted to linear when sampled.
+    Srgb8,
+    /// Three 8-bit sRGB-encoded components and an 8-bit linear alpha
+    /// component, converted to linear when sampled.
+    Srgba8,
+    /// One 16-bit float component.
+    R16F,
+    /// Four 16-bit float components.
+    Rgba16F,
+    /// One 32-bit float component.
+    R32F,
+    /// Four 32-bit float components.
+    Rgba32F,
+    /// One 8-bit unsigned integer component.
+    R8Ui,
+    /// Four 8-bit unsigned integer components.
+    Rgba8Ui,
+    /// One 16-bit unsigned integer component.
+    R16Ui,
+    /// One 32-bit unsigned integer component.
+    R32Ui,
+    /// One 32-bit signed integer component.
+    R32I,
+    /// Four 32-bit unsigned integer components.
+    Rgba32Ui,
+    /// A 16-bit normalized depth component.
+    Depth16,
+    /// A 24-bit normalized depth component, stored in the upper 24 bits of a
+    /// 32-bit unsigned integer.
+    Depth24,
+    /// A 32-bit float depth component.
+    Depth32F,
+    /// A 24-bit normalized depth component in the upper 24 bits and an 8-bit
+    /// stencil component in the lower 8 bits of a 32-bit unsigned integer.
+    Depth24Stencil8,
 }
 
-/// Filter modes for a GPU image.
+/// Filter modes for sampling a GPU image.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ImageFilter {
     /// Nearest neighbor interpolation.
@@ -31,171 +103,338 @@ pub enum ImageFilter {
     Linear,
 }
 
-/// Wrapping modes for a GPU image.
+/// Wrapping modes for sampling a GPU image.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ImageWrap {
     /// Clamps sampling to the bounds of the image.
     Clamp,
     /// Repeats the image.
     Repeat,
+    /// Repeats the image, mirroring every other repetition.
+    MirroredRepeat,
+    /// Returns the sampler's border color outside the bounds of the image.
+    ClampToBorder,
+}
+
+impl ImageFormat {
+    /// Returns the size of a single pixel of this format in bytes.
+    pub fn size(self) -> usize {
+        match self {
+            ImageFormat::R8 | ImageFormat::R8Ui => 1,
+            ImageFormat::Rg8 | ImageFormat::R16F | ImageFormat::R16Ui | ImageFormat::Depth16 => 2,
+            ImageFormat::Rgb8 | ImageFormat::Srgb8 => 3,
+            ImageFormat::Rgba8
+            | ImageFormat::Srgba8
+            | ImageFormat::R32F
+            | ImageFormat::Rgba8Ui
+            | ImageFormat::R32Ui
+            | ImageFormat::R32I
+            | ImageFormat::Depth24
+            | ImageFormat::Depth32F
+            | ImageFormat::Depth24Stencil8 => 4,
+            ImageFormat::Rgba16F => 8,
+            ImageFormat::Rgba32F | ImageFormat::Rgba32Ui => 16,
+        }
+    }
+
+    /// Returns whether this is an integer format.
+    pub fn is_integer(self) -> bool {
+        matches!(
+            self,
+            ImageFormat::R8Ui
+                | ImageFormat::Rgba8Ui
+                | ImageFormat::R16Ui
+                | ImageFormat::R32Ui
+                | ImageFormat::R32I
+                | ImageFormat::Rgba32Ui
+        )
+    }
+
+    /// Returns whether this is a depth or depth/stencil format.
+    pub fn is_depth(self) -> bool {
+        matches!(
+            self,
+            ImageFormat::Depth16
+                | ImageFormat::Depth24
+                | ImageFormat::Depth32F
+                | ImageFormat::Depth24Stencil8
+        )
+    }
 }
 
 impl Image {
     pub(crate) fn new(
         ctx: &Context,
+        kind: ImageKind,
         width: u32,
         height: u32,
-        pixel_format: ImageFormat,
-        filter: ImageFilter,
-        wrap: ImageWrap,
-    ) -> Self {
-        let format = match pixel_format {
-            ImageFormat::Rgb8 => glow::RGB,
-            ImageFormat::Rgba8 => glow::RGBA,
-        };
-        let kind = match pixel_format {
-            ImageFormat::Rgb8 | ImageFormat::Rgba8 => glow::UNSIGNED_BYTE,
-        };
-        let filter = match filter {
-            ImageFilter::Nearest => glow::NEAREST,
-            ImageFilter::Linear => glow::LINEAR,
-        };
-        let wrap = match wrap {
-            ImageWrap::Clamp => glow::CLAMP_TO_EDGE,
-            ImageWrap::Repeat => glow::REPEAT,
-        };
-
-        let inner = unsafe {
-            let texture = ctx.inner.create_texture().unwrap();
-            ctx.inner.bind_texture(glow::TEXTURE_2D, Some(texture)); // TODO: other texture types
-
-            ctx.inner.tex_image_2d(
-                glow::TEXTURE_2D,
-                0,
-                format as _,
-                width as _,
-                height as _,
-                0,
-                format,
-                kind,
-                None,
-            );
-            ctx.inner.generate_mipmap(glow::TEXTURE_2D);
-
-            ctx.inner
-                .tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_MIN_FILTER, filter as _);
-            ctx.inner
-                .tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_MAG_FILTER, filter as _);
-            ctx.inner
-                .tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_WRAP_S, wrap as _);
-            ctx.inner
-                .tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_WRAP_T, wrap as _);
-
-            texture
-        };
-
-        Self {
-            inner,
+        format: ImageFormat,
+        mip_count: u32,
+    ) -> Result<Self, Error> {
+        let desc = ImageDesc {
+            kind,
             width,
             height,
             format,
-            kind,
-            ctx: ctx.inner.clone(),
+            mip_count,
+        };
+        if !Self::valid_size(&desc) {
+            return Err(Error::InvalidSize);
         }
+
+        let id = ctx.backend.create_image(&desc, None)?;
+
+        Ok(Self {
+            internal: Rc::new(ImageInternal {
+                id,
+                backend: ctx.backend.clone(),
+            }),
+            desc,
+        })
     }
 
     pub(crate) fn with_data(
         ctx: &Context,
+        kind: ImageKind,
         width: u32,
         height: u32,
-        pixel_format: ImageFormat,
-        filter: ImageFilter,
-        wrap: ImageWrap,
+        format: ImageFormat,
+        mip_count: u32,
         data: &[u8],
-    ) -> Self {
-        let format = match pixel_format {
-            ImageFormat::Rgb8 => glow::RGB,
-            ImageFormat::Rgba8 => glow::RGBA,
-        };
-        let kind = match pixel_format {
-            ImageFormat::Rgb8 | ImageFormat::Rgba8 => glow::UNSIGNED_BYTE,
-        };
-        let filter = match filter {
-            ImageFilter::Nearest => glow::NEAREST,
-            ImageFilter::Linear => glow::LINEAR,
-        };
-        let wrap = match wrap {
-            ImageWrap::Clamp => glow::CLAMP_TO_EDGE,
-            ImageWrap::Repeat => glow::REPEAT,
+    ) -> Result<Self, Error> {
+        let desc = ImageDesc {
+            kind,
+            width,
+            height,
+            format,
+            mip_count,
         };
+        if !Self::valid_size(&desc) || data.len() != desc.level_len(0) {
+            return Err(Error::InvalidSize);
+        }
 
-        let inner = unsafe {
-            let texture = ctx.inner.create_texture().unwrap();
-            ctx.inner.bind_texture(glow::TEXTURE_2D, Some(texture)); // TODO: other texture types
+        let id = ctx.backend.create_image(&desc, Some(data))?;
 
-            ctx.inner.tex_image_2d(
-                glow::TEXTURE_2D,
-                0,
-                format as _,
-                width as _,
-                height as _,
-                0,
-                format,
-                kind,
-                Some(data),
-            );
-            ctx.inner.generate_mipmap(glow::TEXTURE_2D);
-
-            ctx.inner
-                .tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_MIN_FILTER, filter as _);
-            ctx.inner
-                .tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_MAG_FILTER, filter as _);
-            ctx.inner
-                .tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_WRAP_S, wrap as _);
-            ctx.inner
-                .tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_WRAP_T, wrap as _);
-
-            texture
+        Ok(Self {
+            internal: Rc::new(ImageInternal {
+                id,
+                backend: ctx.backend.clone(),
+            }),
+            desc,
+        })
+    }
+
+    fn valid_size(desc: &ImageDesc) -> bool {
+        let (max_mip_count, layers) = match desc.kind {
+            ImageKind::D2 => (Self::max_mip_count(desc.width, desc.height), 1),
+            ImageKind::Cube if desc.width != desc.height => return false,
+            ImageKind::Cube => (Self::max_mip_count(desc.width, desc.height), 6),
+            ImageKind::Array { layers } => (Self::max_mip_count(desc.width, desc.height), layers),
+            ImageKind::D3 { depth } => (
+                Self::max_mip_count(desc.width.max(depth), desc.height),
+                depth,
+            ),
         };
+        desc.width != 0
+            && desc.height != 0
+            && layers != 0
+            && (1..=max_mip_count).contains(&desc.mip_count)
+    }
 
-        Self {
-            inner,
-            width,
-            height,
-            format,
-            kind,
-            ctx: ctx.inner.clone(),
-        }
+    /// Returns the largest valid mip count for an image of the given size,
+    /// i.e. the number of levels down to and including 1x1.
+    ///
+    /// The depth of 3D images also counts towards their largest mip count.
+    pub fn max_mip_count(width: u32, height: u32) -> u32 {
+        32 - (width | height | 1).leading_zeros()
+    }
+
+    /// Returns the kind of the image.
+    pub fn kind(&self) -> ImageKind {
+        self.desc.kind
+    }
+
+    /// Returns the width of the image in pixels.
+    pub fn width(&self) -> u32 {
+        self.desc.width
+    }
+
+    /// Returns the height of the image in pixels.
+    pub fn height(&self) -> u32 {
+        self.desc.height
+    }
+
+    /// Returns the number of layers of the image: `1` for 2D images, `6` for
+    /// cube maps, and the number of layers or the depth otherwise.
+    pub fn layers(&self) -> u32 {
+        self.desc.level_size(0).2
+    }
+
+    /// Returns the format of the image.
+    pub fn format(&self) -> ImageFormat {
+        self.desc.format
+    }
+
+    /// Returns the number of mip levels of the image.
+    pub fn mip_count(&self) -> u32 {
+        self.desc.mip_count
     }
 
-    /// Updates the contents of the image.
+    /// Updates the contents of every layer of the first mip level of the
+    /// image, in the same layout as the data passed to
+    /// [`Context::create_image_with_data`].
+    ///
+    /// Use [`Image::generate_mipmaps`] or [`Image::update_level`] to update
+    /// the other levels.
+    ///
+    /// # Panics
+    /// Panics if the size of `data` does not match the size of the level.
     pub fn update(&self, data: &[u8]) {
-        self.update_part(0, 0, self.width, self.height, data);
+        self.update_level(0, data);
     }
 
-    /// Updates the contents of a part of the image.
+    /// Updates the contents of a part of the first layer of the first mip
+    /// level of the image.
+    ///
+    /// # Panics
+    /// Panics if the part is out of bounds, or if the size of `data` does not
+    /// match the size of the part.
     pub fn update_part(&self, x: u32, y: u32, width: u32, height: u32, data: &[u8]) {
-        unsafe {
-            self.ctx.bind_texture(glow::TEXTURE_2D, Some(self.inner));
-            self.ctx.tex_sub_image_2d(
-                glow::TEXTURE_2D,
+        assert!(
+            x <= self.desc.width
+                && width <= self.desc.width - x
+                && y <= self.desc.height
+                && height <= self.desc.height - y,
+            "Image region is out of bounds"
+        );
+        assert_eq!(
+            data.len(),
+            width as usize * height as usize * self.desc.format.size(),
+            "Data size does not match the image region"
+        );
+
+        self.internal
+            .backend
+            .update_image(self.internal.id, 0, 0, x, y, width, height, data);
+    }
+
+    /// Updates the contents of every layer of a mip level of the image. Level
+    /// `n` is `max(width >> n, 1)` by `max(height >> n, 1)` pixels, and the
+    /// depth of 3D images shrinks the same way.
+    ///
+    /// # Panics
+    /// Panics if `level` is not less than the mip count, or if the size of
+    /// `data` does not match the size of the level.
+    pub fn update_level(&self, level: u32, data: &[u8]) {
+        assert!(level < self.desc.mip_count, "Mip level is out of bounds");
+        assert_eq!(
+            data.len(),
+            self.desc.level_len(level),
+            "Data size does not match the mip level"
+        );
+
+        let (width, height, layers) = self.desc.level_size(level);
+        for (layer, data) in data.chunks_exact(data.len() / layers as usize).enumerate() {
+            self.internal.backend.update_image(
+                self.internal.id,
+                level,
+                layer as u32,
+                0,
                 0,
-                x as _,
-                y as _,
-                width as _,
-                height as _,
-                self.format,
-                self.kind,
-                glow::PixelUnpackData::Slice(data),
+                width,
+                height,
+                data,
             );
         }
     }
+
+    /// Updates the contents of one layer of a mip level of the image: a face
+    /// of a cube map, a layer of an array, or a slice of a 3D image.
+    ///
+    /// # Panics
+    /// Panics if `level` is not less than the mip count, if `layer` is not
+    /// less than the number of layers in the level, or if the size of `data`
+    /// does not match the size of a layer.
+    pub fn update_layer(&self, level: u32, layer: u32, data: &[u8]) {
+        assert!(level < self.desc.mip_count, "Mip level is out of bounds");
+        let (width, height, layers) = self.desc.level_size(level);
+        assert!(layer < layers, "Layer is out of bounds");
+        assert_eq!(
+            data.len(),
+            self.desc.level_len(level) / layers as usize,
+            "Data size does not match the layer"
+        );
+
+        self.internal.backend.update_image(
+            self.internal.id,
+            level,
+            layer,
+            0,
+            0,
+            width,
+            height,
+            data,
+        );
+    }
+
+    /// Generates the contents of every mip level after the first by
+    /// downsampling the first level.
+    ///
+    /// # Panics
+    /// Panics if the image has an integer or depth format.
+    pub fn generate_mipmaps(&self) {
+        assert!(
+            !self.desc.format.is_integer() && !self.desc.format.is_depth(),
+            "Mipmaps cannot be generated for integer or depth images"
+        );
+
+        self.internal.backend.generate_mipmaps(self.internal.id);
+    }
+
+    /// Reads the contents of the first layer of the first mip level of the
+    /// image into `buf`, in the same layout as the data passed to
+    /// [`Image::update_layer`].
+    ///
+    /// # Panics
+    /// Panics if the size of `buf` does not match the size of a layer.
+    pub fn read(&self, buf: &mut [u8]) {
+        self.read_part(0, 0, self.desc.width, self.desc.height, buf);
+    }
+
+    /// Reads the contents of a part of the first layer of the first mip level
+    /// of the image into `buf`.
+    ///
+    /// # Panics
+    /// Panics if the part is out of bounds, or if the size of `buf` does not
+    /// match the size of the part.
+    pub fn read_part(&self, x: u32, y: u32, width: u32, height: u32, buf: &mut [u8]) {
+        assert!(
+            x <= self.desc.width
+                && width <= self.desc.width - x
+                && y <= self.desc.height
+                && height <= self.desc.height - y,
+            "Image region is out of bounds"
+        );
+        assert_eq!(
+            buf.len(),
+            width as usize * height as usize * self.desc.format.size(),
+            "Buffer size does not match the image region"
+        );
+
+        self.internal
+            .backend
+            .read_image(self.internal.id, x, y, width, height, buf);
+    }
+
+    /// Returns the id of this image in recorded [`Command`](crate::Command)s.
+    #[cfg(feature = "recording")]
+    pub fn id(&self) -> u32 {
+        self.internal.id.0
+    }
 }
 
-impl Drop for Image {
+impl Drop for ImageInternal {
     fn drop(&mut self) {
-        unsafe {
-            self.ctx.delete_texture(self.inner);
-        }
+        self.backend.destroy_image(self.id);
     }
 }
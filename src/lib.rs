@@ -28,18 +28,35 @@
 
 extern crate alloc;
 
+mod backend;
 mod blend;
 mod buffer;
 mod context;
+mod depth_stencil;
+mod error;
 mod image;
 mod pass;
 mod pipeline;
+mod raster;
+mod sampler;
 mod shader;
+mod stream_buffer;
 
+#[cfg(feature = "recording")]
+pub use crate::backend::Command;
+#[cfg(feature = "software")]
+pub use crate::backend::{FragmentInput, SoftwareShader, VertexInput, VertexOutput};
 pub use crate::blend::*;
 pub use crate::buffer::*;
 pub use crate::context::*;
+pub use crate::depth_stencil::*;
+pub use crate::error::*;
 pub use crate::image::*;
 pub use crate::pass::*;
 pub use crate::pipeline::*;
+pub use crate::raster::*;
+pub use crate::sampler::*;
 pub use crate::shader::*;
+pub use crate::stream_buffer::*;
+#[cfg(feature = "derive")]
+pub use fugu_derive::UniformBlock;
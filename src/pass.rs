@@ -1,5 +1,12 @@
+use alloc::rc::Rc;
+use alloc::vec::Vec;
+
+use crate::backend::{Backend, PassId};
+use crate::image::ImageInternal;
+use crate::{Context, Error, Image, ImageKind};
+
 /// A rendering pass action.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum PassAction {
     /// Does nothing.
     Nothing,
@@ -13,3 +20,96 @@ pub enum PassAction {
         stencil: Option<i32>,
     },
 }
+
+/// An offscreen render pass.
+///
+/// A render pass renders into one or more color [`Image`]s and an optional
+/// depth/stencil [`Image`]. All attachments must have the same dimensions.
+/// The attachments are kept alive until the render pass is dropped, even if
+/// their [`Image`]s are dropped first.
+pub struct RenderPass {
+    pub(crate) id: PassId,
+    width: u32,
+    height: u32,
+    _attachments: Vec<Rc<ImageInternal>>,
+    backend: Rc<dyn Backend>,
+}
+
+impl RenderPass {
+    pub(crate) fn new(
+        ctx: &Context,
+        color: &[&Image],
+        depth_stencil: Option<&Image>,
+    ) -> Result<Self, Error> {
+        if color.is_empty() && depth_stencil.is_none() {
+            return Err(Error::NoAttachments);
+        }
+        if !color
+            .iter()
+            .chain(depth_stencil.iter())
+            .all(|image| image.kind() == ImageKind::D2)
+            || color.iter().any(|image| image.format().is_depth())
+            || depth_stencil.map_or(false, |image| !image.format().is_depth())
+        {
+            return Err(Error::InvalidAttachment);
+        }
+
+        let (width, height) = color
+            .first()
+            .or(depth_stencil.as_ref())
+            .map(|image| (image.width(), image.height()))
+            .unwrap();
+        if !color
+            .iter()
+            .chain(depth_stencil.iter())
+            .all(|image| image.width() == width && image.height() == height)
+        {
+            return Err(Error::InvalidSize);
+        }
+
+        let color_ids = color
+            .iter()
+            .map(|image| image.internal.id)
+            .collect::<Vec<_>>();
+        let id = ctx.backend.create_pass(
+            &color_ids,
+            depth_stencil.map(|image| image.internal.id),
+            width,
+            height,
+        )?;
+
+        Ok(Self {
+            id,
+            width,
+            height,
+            _attachments: color
+                .iter()
+                .chain(depth_stencil.iter())
+                .map(|image| image.internal.clone())
+                .collect(),
+            backend: ctx.backend.clone(),
+        })
+    }
+
+    /// Returns the width of the render pass in pixels.
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Returns the height of the render pass in pixels.
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Returns the id of this render pass in recorded [`Command`](crate::Command)s.
+    #[cfg(feature = "recording")]
+    pub fn id(&self) -> u32 {
+        self.id.0
+    }
+}
+
+impl Drop for RenderPass {
+    fn drop(&mut self) {
+        self.backend.destroy_pass(self.id);
+    }
+}
@@ -1,9 +1,7 @@
 use alloc::vec;
 use alloc::vec::Vec;
 
-use glow::HasContext;
-
-use super::{Context, Shader};
+use crate::{BlendState, Context, DepthState, Error, IndexType, RasterState, Shader, StencilState};
 
 /// Step functions for a vertex attribute.
 #[allow(missing_docs)]
@@ -60,31 +58,160 @@ pub struct VertexAttribute {
 #[derive(Clone)]
 pub(crate) struct VertexAttributeInternal {
     pub location: u32,
-    pub format: u32,
+    pub format: VertexFormat,
     pub offset: i32,
     pub stride: i32,
     pub divisor: u32,
-    pub size: i32,
+}
+
+/// Primitive topologies.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PrimitiveType {
+    /// Each vertex is a point.
+    Points,
+    /// Each pair of vertices is a line.
+    Lines,
+    /// Each vertex after the first forms a line with the previous vertex.
+    LineStrip,
+    /// Each three vertices are a triangle.
+    Triangles,
+    /// Each vertex after the second forms a triangle with the previous two
+    /// vertices.
+    TriangleStrip,
+}
+
+impl Default for PrimitiveType {
+    fn default() -> Self {
+        PrimitiveType::Triangles
+    }
+}
+
+/// Fixed-function state of a rendering pipeline.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PipelineState {
+    /// The primitive topology.
+    pub primitive: PrimitiveType,
+    /// The type of the indices in the index buffer, if one is used.
+    pub index_type: IndexType,
+    /// The depth test state.
+    pub depth: DepthState,
+    /// The stencil test state, or `None` to disable stencil testing.
+    pub stencil: Option<StencilState>,
+    /// The rasterizer state.
+    pub raster: RasterState,
+    /// The blend state, or `None` to disable blending.
+    pub blend: Option<BlendState>,
+    /// The blend state for the alpha channel, or `None` to use `blend` for
+    /// both color and alpha. Ignored if `blend` is `None`.
+    pub alpha_blend: Option<BlendState>,
+    /// The constant color used by the `Constant*` blend factors.
+    pub blend_color: (f32, f32, f32, f32),
 }
 
 /// A rendering pipeline.
-#[derive(Clone, Copy, Debug)]
+///
+/// Pipelines are handles to state owned by the [`Context`] that created them,
+/// and stay valid until they are destroyed with
+/// [`Context::destroy_pipeline`]. Using a destroyed pipeline panics, even if
+/// its slot has since been reused by a new pipeline.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct Pipeline {
-    pub(crate) id: usize,
+    index: u32,
+    generation: u32,
 }
 
 pub(crate) struct PipelineInternal {
     pub(crate) attrs: Vec<Vec<VertexAttributeInternal>>,
     pub(crate) shader: Shader,
+    pub(crate) primitive: PrimitiveType,
+    pub(crate) index_type: IndexType,
+    pub(crate) depth: DepthState,
+    pub(crate) stencil: Option<StencilState>,
+    pub(crate) raster: RasterState,
+    pub(crate) blend: Option<BlendState>,
+    pub(crate) alpha_blend: Option<BlendState>,
+    pub(crate) blend_color: (f32, f32, f32, f32),
+}
+
+/// The pipelines of a context, looked up by generational [`Pipeline`]
+/// handles.
+pub(crate) struct Pipelines {
+    slots: Vec<PipelineSlot>,
+    free: Vec<u32>,
+}
+
+struct PipelineSlot {
+    generation: u32,
+    pipeline: Option<PipelineInternal>,
+}
+
+impl Pipelines {
+    pub fn new() -> Self {
+        Self {
+            slots: Vec::new(),
+            free: Vec::new(),
+        }
+    }
+
+    pub fn insert(&mut self, pipeline: PipelineInternal) -> Pipeline {
+        match self.free.pop() {
+            Some(index) => {
+                let slot = &mut self.slots[index as usize];
+                slot.pipeline = Some(pipeline);
+                Pipeline {
+                    index,
+                    generation: slot.generation,
+                }
+            }
+            None => {
+                self.slots.push(PipelineSlot {
+                    generation: 0,
+                    pipeline: Some(pipeline),
+                });
+                Pipeline {
+                    index: (self.slots.len() - 1) as u32,
+                    generation: 0,
+                }
+            }
+        }
+    }
+
+    pub fn get(&self, handle: Pipeline) -> &PipelineInternal {
+        self.slots
+            .get(handle.index as usize)
+            .filter(|slot| slot.generation == handle.generation)
+            .and_then(|slot| slot.pipeline.as_ref())
+            .expect("Pipeline used after being destroyed")
+    }
+
+    pub fn remove(&mut self, handle: Pipeline) -> PipelineInternal {
+        let slot = self
+            .slots
+            .get_mut(handle.index as usize)
+            .filter(|slot| slot.generation == handle.generation)
+            .expect("Pipeline destroyed twice");
+        let pipeline = slot.pipeline.take().expect("Pipeline destroyed twice");
+        // Bumping the generation invalidates every handle to this slot.
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free.push(handle.index);
+        pipeline
+    }
 }
 
 impl Pipeline {
     pub(crate) fn new(
         ctx: &Context,
-        shader: Shader,
+        shader: &Shader,
         buffers: &[BufferLayout],
         attrs: &[VertexAttribute],
-    ) -> Self {
+        state: PipelineState,
+    ) -> Result<Self, Error> {
+        if let Some(attr) = attrs.iter().find(|attr| attr.buffer_index >= buffers.len()) {
+            return Err(Error::InvalidBufferIndex {
+                index: attr.buffer_index,
+            });
+        }
+
         let mut attrs_internal = vec![Vec::new(); buffers.len()];
         let mut offsets = vec![0; buffers.len()];
         let mut strides = buffers.iter().map(|e| e.stride).collect::<Vec<_>>();
@@ -112,21 +239,6 @@ impl Pipeline {
         for attr in attrs {
             let buffer_index = attr.buffer_index;
 
-            let size = match attr.format {
-                VertexFormat::Float1 => 1,
-                VertexFormat::Float2 => 2,
-                VertexFormat::Float3 => 3,
-                VertexFormat::Float4 => 4,
-                VertexFormat::Byte1 => 1,
-                VertexFormat::Byte2 => 2,
-                VertexFormat::Byte3 => 3,
-                VertexFormat::Byte4 => 4,
-                VertexFormat::Short1 => 1,
-                VertexFormat::Short2 => 2,
-                VertexFormat::Short3 => 3,
-                VertexFormat::Short4 => 4,
-            };
-
             let offset = offsets[buffer_index];
             offsets[buffer_index] += match attr.format {
                 VertexFormat::Float1 => 4,
@@ -149,44 +261,33 @@ impl Pipeline {
                 VertexStep::PerInstance(divisor) => divisor,
             };
 
-            let location = unsafe {
-                ctx.inner
-                    .get_attrib_location(shader.inner, attr.name)
-                    .unwrap()
-            };
-
-            let format = match attr.format {
-                VertexFormat::Float1 => glow::FLOAT,
-                VertexFormat::Float2 => glow::FLOAT,
-                VertexFormat::Float3 => glow::FLOAT,
-                VertexFormat::Float4 => glow::FLOAT,
-                VertexFormat::Byte1 => glow::BYTE,
-                VertexFormat::Byte2 => glow::BYTE,
-                VertexFormat::Byte3 => glow::BYTE,
-                VertexFormat::Byte4 => glow::BYTE,
-                VertexFormat::Short1 => glow::SHORT,
-                VertexFormat::Short2 => glow::SHORT,
-                VertexFormat::Short3 => glow::SHORT,
-                VertexFormat::Short4 => glow::SHORT,
-            };
+            let location = ctx
+                .backend
+                .attrib_location(shader.internal.id, attr.name)
+                .ok_or(Error::MissingAttribute { name: attr.name })?;
 
             attrs_internal[buffer_index].push(VertexAttributeInternal {
                 location,
-                format,
+                format: attr.format,
                 offset,
                 stride,
                 divisor,
-                size,
             });
         }
 
-        let pipelines = &mut ctx.state.borrow_mut().pipelines;
-        pipelines.push(PipelineInternal {
+        let pipeline = ctx.state.borrow_mut().pipelines.insert(PipelineInternal {
             attrs: attrs_internal,
-            shader,
+            shader: shader.clone(),
+            primitive: state.primitive,
+            index_type: state.index_type,
+            depth: state.depth,
+            stencil: state.stencil,
+            raster: state.raster,
+            blend: state.blend,
+            alpha_blend: state.alpha_blend,
+            blend_color: state.blend_color,
         });
-        let id = pipelines.len() - 1;
 
-        Pipeline { id }
+        Ok(pipeline)
     }
 }
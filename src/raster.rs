@@ -0,0 +1,50 @@
+/// Face culling modes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CullMode {
+    /// No faces are culled.
+    None,
+    /// Front faces are culled.
+    Front,
+    /// Back faces are culled.
+    Back,
+}
+
+impl Default for CullMode {
+    fn default() -> Self {
+        CullMode::None
+    }
+}
+
+/// Vertex winding orders of front-facing primitives.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrontFace {
+    /// Counter-clockwise winding.
+    Ccw,
+    /// Clockwise winding.
+    Cw,
+}
+
+impl Default for FrontFace {
+    fn default() -> Self {
+        FrontFace::Ccw
+    }
+}
+
+/// Rasterizer state.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct RasterState {
+    /// The face culling mode.
+    pub cull: CullMode,
+    /// The winding order of front-facing primitives.
+    pub front_face: FrontFace,
+    /// The constant depth bias added to each fragment.
+    pub depth_bias: f32,
+    /// The depth bias scaled by the slope of each polygon.
+    pub depth_bias_slope: f32,
+}
+
+impl RasterState {
+    pub(crate) fn depth_bias_enabled(&self) -> bool {
+        self.depth_bias != 0.0 || self.depth_bias_slope != 0.0
+    }
+}
@@ -0,0 +1,101 @@
+use alloc::rc::Rc;
+
+use crate::backend::{Backend, SamplerId};
+use crate::{CompareFunc, Context, Error, ImageFilter, ImageWrap};
+
+/// The state of a [`Sampler`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SamplerState {
+    /// The filter used when the image is minified.
+    pub min_filter: ImageFilter,
+    /// The filter used when the image is magnified.
+    pub mag_filter: ImageFilter,
+    /// The filter used between mip levels, or `None` to only sample the first
+    /// level.
+    pub mipmap_filter: Option<ImageFilter>,
+    /// The wrapping mode of the horizontal texture coordinate.
+    pub wrap_u: ImageWrap,
+    /// The wrapping mode of the vertical texture coordinate.
+    pub wrap_v: ImageWrap,
+    /// The wrapping mode of the depth texture coordinate of 3D images.
+    pub wrap_w: ImageWrap,
+    /// The color returned outside the image by [`ImageWrap::ClampToBorder`].
+    pub border_color: (f32, f32, f32, f32),
+    /// The bias added to the level of detail before clamping.
+    pub lod_bias: f32,
+    /// The minimum level of detail.
+    pub min_lod: f32,
+    /// The maximum level of detail.
+    pub max_lod: f32,
+    /// The maximum degree of anisotropic filtering, clamped to what the
+    /// backend supports. `1` disables anisotropic filtering.
+    pub max_anisotropy: u32,
+    /// The comparison function for sampling depth images, e.g. with a
+    /// `sampler2DShadow`, or `None` to disable comparison.
+    ///
+    /// Sampling returns `1.0` where the texture coordinate's reference value
+    /// compares successfully against the stored depth, and `0.0` elsewhere.
+    pub compare: Option<CompareFunc>,
+}
+
+impl Default for SamplerState {
+    fn default() -> Self {
+        Self {
+            min_filter: ImageFilter::Linear,
+            mag_filter: ImageFilter::Linear,
+            mipmap_filter: None,
+            wrap_u: ImageWrap::Clamp,
+            wrap_v: ImageWrap::Clamp,
+            wrap_w: ImageWrap::Clamp,
+            border_color: (0.0, 0.0, 0.0, 0.0),
+            lod_bias: 0.0,
+            min_lod: 0.0,
+            max_lod: f32::MAX,
+            max_anisotropy: 1,
+            compare: None,
+        }
+    }
+}
+
+/// A GPU sampler, describing how shaders read from an [`Image`](crate::Image).
+///
+/// Samplers are bound alongside images with [`Context::set_images`], so the
+/// same image can be sampled in different ways.
+pub struct Sampler {
+    pub(crate) id: SamplerId,
+    state: SamplerState,
+    backend: Rc<dyn Backend>,
+}
+
+impl Sampler {
+    pub(crate) fn new(ctx: &Context, state: SamplerState) -> Result<Self, Error> {
+        if state.max_anisotropy == 0 || state.min_lod > state.max_lod {
+            return Err(Error::InvalidSamplerState);
+        }
+
+        let id = ctx.backend.create_sampler(&state)?;
+
+        Ok(Self {
+            id,
+            state,
+            backend: ctx.backend.clone(),
+        })
+    }
+
+    /// Returns the state of the sampler.
+    pub fn state(&self) -> SamplerState {
+        self.state
+    }
+
+    /// Returns the id of this sampler in recorded [`Command`](crate::Command)s.
+    #[cfg(feature = "recording")]
+    pub fn id(&self) -> u32 {
+        self.id.0
+    }
+}
+
+impl Drop for Sampler {
+    fn drop(&mut self) {
+        self.backend.destroy_sampler(self.id);
+    }
+}
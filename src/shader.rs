@@ -1,30 +1,51 @@
 use alloc::rc::Rc;
+use alloc::string::String;
 use alloc::vec::Vec;
-use core::str;
+use core::fmt;
 
-use glow::HasContext;
-
-use super::Context;
+use crate::backend::{Backend, ShaderId};
+#[cfg(feature = "software")]
+use crate::backend::{SoftwareBackend, SoftwareShader};
+use crate::{Context, Error};
 
 /// A shader.
+///
+/// Shaders are reference-counted, so cloning a shader is cheap and shares the
+/// compiled program. A shader can be used by any number of pipelines, and is
+/// destroyed once every clone and every pipeline using it is dropped.
+#[derive(Clone)]
 pub struct Shader {
-    pub(crate) inner: glow::Program,
+    pub(crate) internal: Rc<ShaderInternal>,
+}
+
+pub(crate) struct ShaderInternal {
+    pub(crate) id: ShaderId,
     pub(crate) uniforms: Vec<UniformInternal>,
-    pub(crate) image_uniforms: Vec<ImageUniformInternal>,
-    ctx: Rc<glow::Context>,
+    pub(crate) image_count: usize,
+    pub(crate) uniforms_size: usize,
+    backend: Rc<dyn Backend>,
 }
 
 /// A shader uniform.
+///
+/// Uniform data passed to [`Context::set_uniforms`] is read as a tightly
+/// packed sequence of 4-byte scalars: each uniform takes up exactly
+/// [`UniformFormat::size`] times `count` bytes, in declaration order, with no
+/// padding in between. This matches a `#[repr(C)]` struct made up of `f32`,
+/// `i32` and arrays thereof. Note that this differs from the std140 layout,
+/// which pads `vec3`s and matrix columns to 16 bytes.
 pub struct Uniform {
     /// The name of the uniform.
     pub name: &'static str,
     /// The format of the uniform.
     pub format: UniformFormat,
+    /// The number of array elements, or 1 if the uniform is not an array.
+    pub count: usize,
 }
 
 pub(crate) struct UniformInternal {
-    pub location: glow::UniformLocation,
     pub format: UniformFormat,
+    pub count: usize,
 }
 
 /// An image uniform.
@@ -33,13 +54,20 @@ pub struct ImageUniform {
     pub name: &'static str,
 }
 
-pub(crate) struct ImageUniformInternal {
-    pub location: glow::UniformLocation,
+/// A uniform block.
+///
+/// The contents of uniform blocks are provided by uniform buffers bound with
+/// [`Context::set_uniform_buffer`] instead of [`Context::set_uniforms`].
+pub struct UniformBlockBinding {
+    /// The name of the uniform block.
+    pub name: &'static str,
+    /// The binding index the block is sourced from.
+    pub binding: u32,
 }
 
 /// Formats of a shader uniform.
 #[allow(missing_docs)]
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum UniformFormat {
     Float1,
     Float2,
@@ -49,6 +77,156 @@ pub enum UniformFormat {
     Int2,
     Int3,
     Int4,
+    /// A column-major 2x2 matrix.
+    Mat2,
+    /// A column-major 3x3 matrix.
+    Mat3,
+    /// A column-major 4x4 matrix.
+    Mat4,
+}
+
+impl UniformFormat {
+    /// Returns the size of a single element of this format in bytes.
+    pub fn size(self) -> usize {
+        match self {
+            UniformFormat::Float1 | UniformFormat::Int1 => 4,
+            UniformFormat::Float2 | UniformFormat::Int2 => 8,
+            UniformFormat::Float3 | UniformFormat::Int3 => 12,
+            UniformFormat::Float4 | UniformFormat::Int4 | UniformFormat::Mat2 => 16,
+            UniformFormat::Mat3 => 36,
+            UniformFormat::Mat4 => 64,
+        }
+    }
+}
+
+/// A type that can be uploaded as a single uniform element.
+///
+/// # Safety
+/// The type must have the exact size of `FORMAT` and contain no padding or
+/// non-`f32`/`i32` data.
+pub unsafe trait UniformField {
+    /// The format of the uniform.
+    const FORMAT: UniformFormat;
+}
+
+macro_rules! impl_uniform_field {
+    ($($ty:ty => $format:ident,)*) => {
+        $(
+            unsafe impl UniformField for $ty {
+                const FORMAT: UniformFormat = UniformFormat::$format;
+            }
+        )*
+    };
+}
+
+impl_uniform_field! {
+    f32 => Float1,
+    [f32; 2] => Float2,
+    [f32; 3] => Float3,
+    [f32; 4] => Float4,
+    i32 => Int1,
+    [i32; 2] => Int2,
+    [i32; 3] => Int3,
+    [i32; 4] => Int4,
+    [[f32; 2]; 2] => Mat2,
+    [[f32; 3]; 3] => Mat3,
+    [[f32; 4]; 4] => Mat4,
+}
+
+/// A type describing the layout of the data passed to
+/// [`Context::set_uniforms`].
+///
+/// Any [`UniformField`] is a block containing a single uniform. For structs,
+/// this trait can be derived with the `derive` feature enabled; fields must be
+/// [`UniformField`]s, or arrays of them marked with `#[uniform(array)]`, and
+/// the struct must be `#[repr(C)]`:
+///
+#[cfg_attr(feature = "derive", doc = "```")]
+#[cfg_attr(not(feature = "derive"), doc = "```ignore")]
+/// use fugu::{UniformBlock, UniformFormat};
+///
+/// #[derive(UniformBlock)]
+/// #[repr(C)]
+/// struct Uniforms {
+///     mvp: [[f32; 4]; 4],
+///     #[uniform(array)]
+///     lights: [[f32; 4]; 8],
+/// }
+///
+/// assert_eq!(
+///     Uniforms::LAYOUT,
+///     &[(UniformFormat::Mat4, 1), (UniformFormat::Float4, 8)],
+/// );
+/// ```
+///
+/// Structs without a C layout are rejected, as their fields may be reordered:
+///
+#[cfg_attr(feature = "derive", doc = "```compile_fail")]
+#[cfg_attr(not(feature = "derive"), doc = "```ignore")]
+/// use fugu::UniformBlock;
+///
+/// #[derive(UniformBlock)]
+/// struct Uniforms {
+///     color: [f32; 4],
+///     scale: f32,
+/// }
+/// ```
+///
+/// The layout is checked against the uniforms of the current pipeline's
+/// shader by [`Context::set_uniforms`].
+///
+/// # Safety
+/// The type must follow the packed layout described in [`Uniform`], with
+/// each entry of `LAYOUT` covering exactly the corresponding bytes.
+pub unsafe trait UniformBlock {
+    /// The format and array element count of each uniform, in order.
+    const LAYOUT: &'static [(UniformFormat, usize)];
+}
+
+unsafe impl<T: UniformField> UniformBlock for T {
+    const LAYOUT: &'static [(UniformFormat, usize)] = &[(T::FORMAT, 1)];
+}
+
+/// Stages of a shader program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShaderStage {
+    /// The vertex shader.
+    Vertex,
+    /// The fragment shader.
+    Fragment,
+}
+
+impl fmt::Display for ShaderStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShaderStage::Vertex => f.write_str("vertex"),
+            ShaderStage::Fragment => f.write_str("fragment"),
+        }
+    }
+}
+
+/// A single message from a shader compile or link log.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShaderDiagnostic {
+    /// The source line the message refers to, if the driver reported one.
+    pub line: Option<u32>,
+    /// The column the message refers to, if the driver reported one along
+    /// with the line.
+    pub column: Option<u32>,
+    /// The message, without the location prefix.
+    pub message: String,
+}
+
+impl fmt::Display for ShaderDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.line, self.column) {
+            (Some(line), Some(column)) => {
+                write!(f, "line {}, column {}: {}", line, column, self.message)
+            }
+            (Some(line), None) => write!(f, "line {}: {}", line, self.message),
+            _ => f.write_str(&self.message),
+        }
+    }
 }
 
 impl Shader {
@@ -58,66 +236,73 @@ impl Shader {
         frag_source: impl AsRef<[u8]>,
         uniforms: &[Uniform],
         images: &[ImageUniform],
-    ) -> Self {
-        let inner = unsafe {
-            let program = ctx.inner.create_program().unwrap();
-
-            let vert = ctx.inner.create_shader(glow::VERTEX_SHADER).unwrap();
-            let frag = ctx.inner.create_shader(glow::FRAGMENT_SHADER).unwrap();
-
-            ctx.inner
-                .shader_source(vert, str::from_utf8(vert_source.as_ref()).unwrap());
-            ctx.inner
-                .shader_source(frag, str::from_utf8(frag_source.as_ref()).unwrap());
-
-            ctx.inner.compile_shader(vert);
-            ctx.inner.compile_shader(frag);
-
-            ctx.inner.attach_shader(program, vert);
-            ctx.inner.attach_shader(program, frag);
-
-            ctx.inner.link_program(program);
-
-            ctx.inner.delete_shader(vert);
-            ctx.inner.delete_shader(frag);
-
-            ctx.inner.use_program(Some(program));
-
-            program
-        };
-
-        let uniforms = uniforms
-            .iter()
-            .map(|uniform| UniformInternal {
-                location: unsafe { ctx.inner.get_uniform_location(inner, uniform.name).unwrap() },
-                format: uniform.format,
-            })
-            .collect();
-
-        let image_uniforms = images
-            .iter()
-            .map(|image_uniform| ImageUniformInternal {
-                location: unsafe {
-                    ctx.inner
-                        .get_uniform_location(inner, image_uniform.name)
-                        .unwrap()
-                },
-            })
-            .collect();
-
-        Self {
-            inner,
-            uniforms,
-            image_uniforms,
-            ctx: ctx.inner.clone(),
+        uniform_blocks: &[UniformBlockBinding],
+    ) -> Result<Self, Error> {
+        Self::create(ctx, uniforms, images, || {
+            ctx.backend.create_shader(
+                vert_source.as_ref(),
+                frag_source.as_ref(),
+                uniforms,
+                images,
+                uniform_blocks,
+            )
+        })
+    }
+
+    #[cfg(feature = "software")]
+    pub(crate) fn new_software(
+        ctx: &Context,
+        backend: &SoftwareBackend,
+        shader: SoftwareShader,
+        uniforms: &[Uniform],
+        images: &[ImageUniform],
+    ) -> Result<Self, Error> {
+        Self::create(ctx, uniforms, images, || {
+            Ok(backend.create_software_shader(shader))
+        })
+    }
+
+    fn create(
+        ctx: &Context,
+        uniforms: &[Uniform],
+        images: &[ImageUniform],
+        create_shader: impl FnOnce() -> Result<ShaderId, Error>,
+    ) -> Result<Self, Error> {
+        if uniforms.iter().any(|uniform| uniform.count == 0) {
+            return Err(Error::InvalidSize);
         }
+
+        let id = create_shader()?;
+
+        Ok(Self {
+            internal: Rc::new(ShaderInternal {
+                id,
+                uniforms: uniforms
+                    .iter()
+                    .map(|uniform| UniformInternal {
+                        format: uniform.format,
+                        count: uniform.count,
+                    })
+                    .collect(),
+                image_count: images.len(),
+                uniforms_size: uniforms
+                    .iter()
+                    .map(|uniform| uniform.format.size() * uniform.count)
+                    .sum(),
+                backend: ctx.backend.clone(),
+            }),
+        })
+    }
+
+    /// Returns the id of this shader in recorded [`Command`](crate::Command)s.
+    #[cfg(feature = "recording")]
+    pub fn id(&self) -> u32 {
+        self.internal.id.0
     }
 }
 
-impl Drop for Shader {
+impl Drop for ShaderInternal {
     fn drop(&mut self) {
-        unsafe {
-            self.ctx.delete_program(self.inner);
-        }
+        self.backend.destroy_shader(self.id);
     }
 }
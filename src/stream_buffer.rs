@@ -0,0 +1,100 @@
+use alloc::rc::Rc;
+use alloc::vec::Vec;
+use core::cell::{Cell, RefCell};
+use core::mem;
+
+use crate::context::ContextState;
+use crate::{Buffer, BufferKind, BufferUsage, Context, Error};
+
+/// A ring of [`BufferUsage::Stream`] buffers for geometry or uniforms that
+/// change every frame.
+///
+/// Data is appended to the buffer of the current frame with
+/// [`StreamBuffer::push`], which returns the buffer and offset to bind, e.g.
+/// with [`Context::set_vertex_buffers_with_offsets`]. After
+/// [`Context::commit_frame`], the next push moves on to the next buffer in the
+/// ring, so a buffer is only rewritten `frame_count` frames after it was last
+/// used. It is also invalidated before being rewritten, so the backend can
+/// hand out fresh storage instead of stalling if the GPU falls further behind.
+pub struct StreamBuffer {
+    buffers: Vec<Buffer>,
+    align: usize,
+    state: Rc<RefCell<ContextState>>,
+    frame: Cell<u64>,
+    current: Cell<usize>,
+    cursor: Cell<usize>,
+}
+
+impl StreamBuffer {
+    pub(crate) fn new(
+        ctx: &Context,
+        kind: BufferKind,
+        size: usize,
+        frame_count: usize,
+    ) -> Result<Self, Error> {
+        if frame_count == 0 {
+            return Err(Error::InvalidSize);
+        }
+
+        let buffers = (0..frame_count)
+            .map(|_| Buffer::new(ctx, kind, BufferUsage::Stream, size))
+            .collect::<Result<Vec<_>, _>>()?;
+        // Uniform buffers are bound at offsets with a backend specific
+        // alignment, while vertex attributes and indices are at most 4 bytes.
+        let align = match kind {
+            BufferKind::Uniform => ctx.uniform_buffer_offset_alignment(),
+            BufferKind::Vertex | BufferKind::Index => 4,
+        };
+
+        Ok(Self {
+            buffers,
+            align,
+            state: ctx.state.clone(),
+            frame: Cell::new(ctx.state.borrow().frame),
+            current: Cell::new(0),
+            cursor: Cell::new(0),
+        })
+    }
+
+    /// Appends `data` to the buffer of the current frame, returning the
+    /// buffer and the offset of the data in bytes, or `None` if there is not
+    /// enough space left in this frame.
+    ///
+    /// Offsets are aligned to 4 bytes, or to
+    /// [`Context::uniform_buffer_offset_alignment`] for uniform buffers.
+    pub fn push<T>(&self, data: &[T]) -> Option<(&Buffer, usize)> {
+        let frame = self.state.borrow().frame;
+        if frame != self.frame.get() {
+            let current = (self.current.get() + 1) % self.buffers.len();
+            self.frame.set(frame);
+            self.current.set(current);
+            self.cursor.set(0);
+            self.buffers[current].invalidate();
+        }
+
+        let buffer = &self.buffers[self.current.get()];
+        let offset = align_up(self.cursor.get(), self.align);
+        let end = offset.checked_add(mem::size_of_val(data))?;
+        if end > buffer.size {
+            return None;
+        }
+
+        buffer.update_range(offset, data);
+        self.cursor.set(end);
+        Some((buffer, offset))
+    }
+
+    /// Returns the number of bytes available in each frame.
+    pub fn size(&self) -> usize {
+        self.buffers[0].size
+    }
+
+    /// Returns the number of buffers in the ring.
+    pub fn frame_count(&self) -> usize {
+        self.buffers.len()
+    }
+}
+
+fn align_up(offset: usize, align: usize) -> usize {
+    (offset + align - 1) / align * align
+}
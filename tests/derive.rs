@@ -0,0 +1,107 @@
+#![cfg(all(feature = "derive", feature = "recording"))]
+
+use std::mem;
+
+use fugu::*;
+
+#[derive(UniformBlock)]
+#[repr(C)]
+struct Uniforms {
+    mvp: [[f32; 4]; 4],
+    tint: [f32; 3],
+    #[uniform(array)]
+    offsets: [[f32; 2]; 4],
+    mode: i32,
+}
+
+fn shader(ctx: &Context, uniforms: &[Uniform]) -> Shader {
+    ctx.create_shader("", "", uniforms, &[], &[])
+}
+
+fn pipeline(ctx: &Context, shader: &Shader) -> Pipeline {
+    ctx.create_pipeline(shader, &[], &[], PipelineState::default())
+}
+
+const UNIFORMS: &[Uniform] = &[
+    Uniform {
+        name: "mvp",
+        format: UniformFormat::Mat4,
+        count: 1,
+    },
+    Uniform {
+        name: "tint",
+        format: UniformFormat::Float3,
+        count: 1,
+    },
+    Uniform {
+        name: "offsets",
+        format: UniformFormat::Float2,
+        count: 4,
+    },
+    Uniform {
+        name: "mode",
+        format: UniformFormat::Int1,
+        count: 1,
+    },
+];
+
+#[test]
+fn derived_layout_matches_fields() {
+    assert_eq!(
+        Uniforms::LAYOUT,
+        &[
+            (UniformFormat::Mat4, 1),
+            (UniformFormat::Float3, 1),
+            (UniformFormat::Float2, 4),
+            (UniformFormat::Int1, 1),
+        ]
+    );
+    let size = Uniforms::LAYOUT
+        .iter()
+        .map(|(format, count)| format.size() * count)
+        .sum::<usize>();
+    assert_eq!(size, mem::size_of::<Uniforms>());
+}
+
+#[test]
+fn derived_block_uploads_packed_data() {
+    let ctx = Context::new_recording();
+    let shader = shader(&ctx, UNIFORMS);
+    let pipeline = pipeline(&ctx, &shader);
+    ctx.set_pipeline(&pipeline);
+    ctx.set_uniforms(Uniforms {
+        mvp: [[0.0; 4]; 4],
+        tint: [1.0, 2.0, 3.0],
+        offsets: [[0.0; 2]; 4],
+        mode: 7,
+    });
+
+    match ctx.recorded_commands().last() {
+        Some(Command::SetUniforms { data, .. }) => {
+            assert_eq!(data.len(), mem::size_of::<Uniforms>());
+            assert_eq!(data[64..68], 1.0f32.to_ne_bytes());
+            assert_eq!(data[data.len() - 4..], 7i32.to_ne_bytes());
+        }
+        command => panic!("unexpected command {:?}", command),
+    }
+}
+
+#[test]
+#[should_panic(expected = "does not match the shader's uniforms")]
+fn mismatched_derived_block_panics() {
+    #[derive(UniformBlock)]
+    #[repr(C)]
+    struct Wrong {
+        mvp: [[f32; 4]; 4],
+        tint: [f32; 4],
+    }
+
+    let ctx = Context::new_recording();
+    let shader = shader(&ctx, UNIFORMS);
+    let pipeline = pipeline(&ctx, &shader);
+    ctx.set_pipeline(&pipeline);
+    ctx.set_uniforms(Wrong {
+        mvp: [[0.0; 4]; 4],
+        tint: [0.0; 4],
+    });
+}
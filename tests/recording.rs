@@ -0,0 +1,340 @@
+#![cfg(feature = "recording")]
+
+use fugu::*;
+
+fn textured_shader(ctx: &Context) -> Shader {
+    ctx.create_shader("", "", &[], &[ImageUniform { name: "tex" }], &[])
+}
+
+fn pipeline(ctx: &Context, shader: &Shader) -> Pipeline {
+    ctx.create_pipeline(
+        shader,
+        &[BufferLayout {
+            stride: 8,
+            ..Default::default()
+        }],
+        &[VertexAttribute {
+            name: "pos",
+            format: VertexFormat::Float2,
+            buffer_index: 0,
+        }],
+        PipelineState {
+            index_type: IndexType::U16,
+            ..Default::default()
+        },
+    )
+}
+
+#[test]
+fn indexed_draw_records_bound_state() {
+    let ctx = Context::new_recording();
+    let shader = textured_shader(&ctx);
+    // Create a pipeline first, so the one drawn with is not the first slot.
+    let unused = pipeline(&ctx, &shader);
+    let pipeline = pipeline(&ctx, &shader);
+    let vertices =
+        ctx.create_buffer_with_data(BufferKind::Vertex, BufferUsage::Static, &[[0.0f32; 2]; 4]);
+    let indices = ctx.create_buffer_with_data(
+        BufferKind::Index,
+        BufferUsage::Static,
+        &[0u16, 1, 2, 0, 2, 3],
+    );
+    let image = ctx.create_image(ImageKind::D2, 4, 4, ImageFormat::Rgba8, 1);
+    let sampler = ctx.create_sampler(SamplerState::default());
+    ctx.clear_recorded_commands();
+
+    ctx.begin_default_pass(PassAction::Nothing);
+    ctx.set_pipeline(&pipeline);
+    ctx.set_vertex_buffer(&vertices);
+    ctx.set_index_buffer(&indices);
+    ctx.set_images(&[(&image, &sampler)]);
+    ctx.draw(0, 6, 1);
+    ctx.end_render_pass();
+
+    let commands = ctx.recorded_commands();
+    assert_eq!(
+        commands[commands.len() - 2],
+        Command::Draw {
+            primitive: PrimitiveType::Triangles,
+            index_type: Some(IndexType::U16),
+            start: 0,
+            count: 6,
+            instances: 1,
+            pipeline,
+            vertex_buffers: vec![(vertices.id(), 0)],
+            index_buffer: Some(indices.id()),
+            images: vec![(image.id(), sampler.id())],
+        }
+    );
+    assert_ne!(pipeline, unused);
+}
+
+#[test]
+fn index_buffer_offset_moves_start() {
+    let ctx = Context::new_recording();
+    let shader = textured_shader(&ctx);
+    let pipeline = pipeline(&ctx, &shader);
+    let indices = ctx.create_buffer(BufferKind::Index, BufferUsage::Dynamic, 64);
+    ctx.set_pipeline(&pipeline);
+    ctx.set_index_buffer_with_offset(&indices, 12);
+    ctx.draw(1, 3, 1);
+
+    match ctx.recorded_commands().last() {
+        Some(Command::Draw { start, count, .. }) => assert_eq!((*start, *count), (7, 3)),
+        command => panic!("unexpected command {:?}", command),
+    }
+}
+
+#[test]
+#[should_panic(expected = "Pipeline destroyed twice")]
+fn destroying_pipeline_twice_panics() {
+    let ctx = Context::new_recording();
+    let shader = textured_shader(&ctx);
+    let pipeline = pipeline(&ctx, &shader);
+    ctx.destroy_pipeline(pipeline);
+    ctx.destroy_pipeline(pipeline);
+}
+
+#[test]
+#[should_panic(expected = "Pipeline used after being destroyed")]
+fn using_destroyed_pipeline_panics() {
+    let ctx = Context::new_recording();
+    let shader = textured_shader(&ctx);
+    let pipeline = pipeline(&ctx, &shader);
+    ctx.destroy_pipeline(pipeline);
+    ctx.set_pipeline(&pipeline);
+}
+
+#[test]
+#[should_panic(expected = "Pipeline used after being destroyed")]
+fn reused_pipeline_slot_rejects_stale_handle() {
+    let ctx = Context::new_recording();
+    let shader = textured_shader(&ctx);
+    let stale = pipeline(&ctx, &shader);
+    ctx.destroy_pipeline(stale);
+    let _fresh = pipeline(&ctx, &shader);
+    ctx.set_pipeline(&stale);
+}
+
+#[test]
+fn dropped_resources_are_released() {
+    let ctx = Context::new_recording();
+    {
+        let shader = textured_shader(&ctx);
+        let pipeline = pipeline(&ctx, &shader);
+        let _buffer = ctx.create_buffer(BufferKind::Vertex, BufferUsage::Dynamic, 16);
+        let image = ctx.create_image(ImageKind::D2, 4, 4, ImageFormat::Rgba8, 1);
+        let depth = ctx.create_image(ImageKind::D2, 4, 4, ImageFormat::Depth24, 1);
+        let _sampler = ctx.create_sampler(SamplerState::default());
+        let _pass = ctx.create_render_pass(&[&image], Some(&depth));
+        assert_eq!(ctx.recorded_live_resources(), 6);
+
+        // The pipeline keeps its shader alive after the handle is dropped.
+        drop(shader);
+        assert_eq!(ctx.recorded_live_resources(), 6);
+        ctx.destroy_pipeline(pipeline);
+        assert_eq!(ctx.recorded_live_resources(), 5);
+    }
+    assert_eq!(ctx.recorded_live_resources(), 0);
+}
+
+#[test]
+fn render_pass_keeps_attachments_alive() {
+    let ctx = Context::new_recording();
+    let image = ctx.create_image(ImageKind::D2, 4, 4, ImageFormat::Rgba8, 1);
+    let image_id = image.id();
+    let pass = ctx.create_render_pass(&[&image], None);
+    let pass_id = pass.id();
+    ctx.clear_recorded_commands();
+
+    drop(image);
+    assert_eq!(ctx.recorded_live_resources(), 2);
+    assert_eq!(ctx.recorded_commands(), []);
+
+    drop(pass);
+    assert_eq!(ctx.recorded_live_resources(), 0);
+    assert_eq!(
+        ctx.recorded_commands(),
+        [
+            Command::DestroyPass { pass: pass_id },
+            Command::DestroyImage { image: image_id },
+        ]
+    );
+}
+
+#[test]
+fn out_of_range_buffer_index_is_an_error() {
+    let ctx = Context::new_recording();
+    let shader = textured_shader(&ctx);
+    let result = ctx.try_create_pipeline(
+        &shader,
+        &[],
+        &[VertexAttribute {
+            name: "pos",
+            format: VertexFormat::Float2,
+            buffer_index: 0,
+        }],
+        PipelineState::default(),
+    );
+    assert_eq!(result, Err(Error::InvalidBufferIndex { index: 0 }));
+}
+
+#[test]
+fn update_range_records_offset() {
+    let ctx = Context::new_recording();
+    let buffer = ctx.create_buffer(BufferKind::Vertex, BufferUsage::Dynamic, 16);
+    buffer.update_range(8, &[1u16, 2]);
+    buffer.update(&[3u8]);
+
+    let commands = ctx.recorded_commands();
+    assert_eq!(
+        commands[commands.len() - 2..],
+        [
+            Command::UpdateBuffer {
+                buffer: buffer.id(),
+                offset: 8,
+                data: [1u16.to_ne_bytes(), 2u16.to_ne_bytes()].concat(),
+            },
+            Command::UpdateBuffer {
+                buffer: buffer.id(),
+                offset: 0,
+                data: vec![3],
+            },
+        ]
+    );
+}
+
+#[test]
+#[should_panic(expected = "Update range is out of bounds")]
+fn update_range_past_end_panics() {
+    let ctx = Context::new_recording();
+    let buffer = ctx.create_buffer(BufferKind::Vertex, BufferUsage::Dynamic, 16);
+    buffer.update_range(12, &[0u32; 2]);
+}
+
+#[test]
+#[should_panic(expected = "Update range is out of bounds")]
+fn update_range_offset_overflow_panics() {
+    let ctx = Context::new_recording();
+    let buffer = ctx.create_buffer(BufferKind::Vertex, BufferUsage::Dynamic, 16);
+    buffer.update_range(usize::MAX, &[0u8]);
+}
+
+#[test]
+#[should_panic(expected = "Update range is out of bounds")]
+fn update_larger_than_buffer_panics() {
+    let ctx = Context::new_recording();
+    let buffer = ctx.create_buffer(BufferKind::Vertex, BufferUsage::Dynamic, 16);
+    buffer.update(&[0u8; 17]);
+}
+
+#[test]
+fn stream_buffers_can_be_invalidated() {
+    let ctx = Context::new_recording();
+    let buffer = ctx.create_buffer(BufferKind::Vertex, BufferUsage::Stream, 16);
+    buffer.invalidate();
+    assert_eq!(
+        ctx.recorded_commands().last(),
+        Some(&Command::InvalidateBuffer {
+            buffer: buffer.id()
+        })
+    );
+}
+
+#[test]
+#[should_panic(expected = "Only stream buffers can be invalidated")]
+fn invalidating_dynamic_buffer_panics() {
+    let ctx = Context::new_recording();
+    let buffer = ctx.create_buffer(BufferKind::Vertex, BufferUsage::Dynamic, 16);
+    buffer.invalidate();
+}
+
+#[test]
+fn stream_buffer_aligns_vertex_offsets() {
+    let ctx = Context::new_recording();
+    let stream = ctx.create_stream_buffer(BufferKind::Vertex, 64, 2);
+    let offsets = [
+        stream.push(&[0u8; 3]).unwrap().1,
+        stream.push(&[0u16; 1]).unwrap().1,
+        stream.push(&[0u8; 1]).unwrap().1,
+    ];
+    assert_eq!(offsets, [0, 4, 8]);
+}
+
+#[test]
+fn stream_buffer_aligns_uniform_offsets() {
+    let ctx = Context::new_recording();
+    let align = ctx.uniform_buffer_offset_alignment();
+    let stream = ctx.create_stream_buffer(BufferKind::Uniform, align * 4, 2);
+    let offsets = [
+        stream.push(&[0f32; 4]).unwrap().1,
+        stream.push(&[0f32; 1]).unwrap().1,
+    ];
+    assert_eq!(offsets, [0, align]);
+}
+
+#[test]
+fn stream_buffer_returns_none_when_full() {
+    let ctx = Context::new_recording();
+    let stream = ctx.create_stream_buffer(BufferKind::Vertex, 16, 2);
+    assert_eq!(stream.push(&[0u8; 9]).map(|(_, offset)| offset), Some(0));
+    // The next push starts at offset 12 after alignment.
+    assert!(stream.push(&[0u8; 5]).is_none());
+    assert_eq!(stream.push(&[0u8; 4]).map(|(_, offset)| offset), Some(12));
+    assert!(stream.push(&[0u8; 1]).is_none());
+}
+
+#[test]
+fn stream_buffer_cycles_and_invalidates_after_commit() {
+    let ctx = Context::new_recording();
+    let stream = ctx.create_stream_buffer(BufferKind::Vertex, 16, 2);
+    ctx.clear_recorded_commands();
+
+    let (first, offset) = stream.push(&[1u32]).unwrap();
+    let first = first.id();
+    assert_eq!(offset, 0);
+    assert_eq!(stream.push(&[2u32]).unwrap().0.id(), first);
+    assert!(!ctx
+        .recorded_commands()
+        .iter()
+        .any(|command| matches!(command, Command::InvalidateBuffer { .. })));
+
+    ctx.commit_frame();
+    ctx.clear_recorded_commands();
+    let (second, offset) = stream.push(&[3u32]).unwrap();
+    let second = second.id();
+    assert_ne!(second, first);
+    assert_eq!(offset, 0);
+    assert_eq!(
+        ctx.recorded_commands(),
+        [
+            Command::InvalidateBuffer { buffer: second },
+            Command::UpdateBuffer {
+                buffer: second,
+                offset: 0,
+                data: 3u32.to_ne_bytes().to_vec(),
+            },
+        ]
+    );
+
+    // The ring wraps around to the first buffer, which is reused from the
+    // start after being invalidated.
+    ctx.commit_frame();
+    ctx.clear_recorded_commands();
+    let (buffer, offset) = stream.push(&[4u32]).unwrap();
+    assert_eq!((buffer.id(), offset), (first, 0));
+    assert_eq!(
+        ctx.recorded_commands()[0],
+        Command::InvalidateBuffer { buffer: first }
+    );
+}
+
+#[test]
+fn stream_buffer_without_frames_is_an_error() {
+    let ctx = Context::new_recording();
+    assert_eq!(
+        ctx.try_create_stream_buffer(BufferKind::Vertex, 16, 0)
+            .err(),
+        Some(Error::InvalidSize)
+    );
+}
@@ -0,0 +1,526 @@
+#![cfg(feature = "software")]
+
+use fugu::*;
+
+/// A vertex with a position and a color.
+type Vertex = [f32; 7];
+
+fn color_shader(ctx: &Context) -> Shader {
+    ctx.create_software_shader(
+        SoftwareShader {
+            attributes: &["pos", "color"],
+            vertex: Box::new(|input| {
+                let [x, y, z, _] = input.attributes[0];
+                VertexOutput {
+                    position: [x, y, z, 1.0],
+                    varyings: input.attributes[1].to_vec(),
+                }
+            }),
+            fragment: Box::new(|input| {
+                let v = input.varyings;
+                Some([v[0], v[1], v[2], v[3]])
+            }),
+        },
+        &[],
+        &[],
+    )
+}
+
+/// Creates a shader covering the target with a fragment stage computing the
+/// color from the window position.
+fn texture_shader(
+    ctx: &Context,
+    fragment: impl Fn(&FragmentInput, [f32; 2]) -> [f32; 4] + 'static,
+) -> Shader {
+    ctx.create_software_shader(
+        SoftwareShader {
+            attributes: &["pos", "color"],
+            vertex: Box::new(|input| {
+                let [x, y, z, _] = input.attributes[0];
+                VertexOutput {
+                    position: [x, y, z, 1.0],
+                    varyings: Vec::new(),
+                }
+            }),
+            fragment: Box::new(move |input| {
+                let [x, y, _, _] = input.position;
+                Some(fragment(input, [x, y]))
+            }),
+        },
+        &[],
+        &[ImageUniform { name: "tex" }],
+    )
+}
+
+fn pipeline(ctx: &Context, shader: &Shader, state: PipelineState) -> Pipeline {
+    ctx.create_pipeline(
+        shader,
+        &[BufferLayout {
+            stride: 28,
+            ..Default::default()
+        }],
+        &[
+            VertexAttribute {
+                name: "pos",
+                format: VertexFormat::Float3,
+                buffer_index: 0,
+            },
+            VertexAttribute {
+                name: "color",
+                format: VertexFormat::Float4,
+                buffer_index: 0,
+            },
+        ],
+        state,
+    )
+}
+
+fn draw(ctx: &Context, pipeline: &Pipeline, vertices: &[Vertex]) {
+    let buffer = ctx.create_buffer_with_data(BufferKind::Vertex, BufferUsage::Static, vertices);
+    ctx.set_pipeline(pipeline);
+    ctx.set_vertex_buffer(&buffer);
+    ctx.draw(0, vertices.len(), 1);
+}
+
+/// A triangle covering the whole target at the given depth.
+fn fullscreen(z: f32, [r, g, b, a]: [f32; 4]) -> [Vertex; 3] {
+    [
+        [-1.0, -1.0, z, r, g, b, a],
+        [3.0, -1.0, z, r, g, b, a],
+        [-1.0, 3.0, z, r, g, b, a],
+    ]
+}
+
+fn clear(color: (f32, f32, f32, f32)) -> PassAction {
+    PassAction::Clear {
+        color: Some(color),
+        depth: Some(1.0),
+        stencil: Some(0),
+    }
+}
+
+/// Reads the default framebuffer, bottom row first.
+fn pixels(ctx: &Context, width: u32, height: u32) -> Vec<[u8; 4]> {
+    let mut buf = vec![0; width as usize * height as usize * 4];
+    ctx.read_pixels(0, 0, width, height, &mut buf);
+    buf.chunks_exact(4)
+        .map(|pixel| [pixel[0], pixel[1], pixel[2], pixel[3]])
+        .collect()
+}
+
+const BLACK: [u8; 4] = [0, 0, 0, 255];
+const RED: [u8; 4] = [255, 0, 0, 255];
+const GREEN: [u8; 4] = [0, 255, 0, 255];
+const BLUE: [u8; 4] = [0, 0, 255, 255];
+
+#[test]
+fn glsl_shaders_are_unsupported() {
+    let ctx = Context::new_software(1, 1);
+    let result = ctx.try_create_shader("", "", &[], &[], &[]);
+    assert_eq!(result.err(), Some(Error::Unsupported));
+}
+
+#[test]
+fn triangle_coverage_follows_top_left_rule() {
+    let ctx = Context::new_software(4, 4);
+    let shader = color_shader(&ctx);
+    let pipeline = pipeline(&ctx, &shader, PipelineState::default());
+    // The hypotenuse passes through the centers of the pixels with
+    // x + y == 3, which belong to the triangle on its top-right side.
+    ctx.begin_default_pass(clear((0.0, 0.0, 0.0, 1.0)));
+    draw(
+        &ctx,
+        &pipeline,
+        &[
+            [-1.0, -1.0, 0.0, 1.0, 0.0, 0.0, 1.0],
+            [1.0, -1.0, 0.0, 1.0, 0.0, 0.0, 1.0],
+            [-1.0, 1.0, 0.0, 1.0, 0.0, 0.0, 1.0],
+        ],
+    );
+    ctx.end_render_pass();
+
+    let expected = (0..16)
+        .map(|i| if i % 4 + i / 4 < 3 { RED } else { BLACK })
+        .collect::<Vec<_>>();
+    assert_eq!(pixels(&ctx, 4, 4), expected);
+
+    // Two triangles sharing an edge cover each pixel exactly once.
+    let additive = PipelineState {
+        blend: Some(BlendState {
+            op: BlendOp::Add,
+            source: BlendFactor::One,
+            dest: BlendFactor::One,
+        }),
+        ..Default::default()
+    };
+    let pipeline = self::pipeline(&ctx, &shader, additive);
+    let quarter = [0.25, 0.0, 0.0, 0.0];
+    let corner = |x: f32, y: f32| {
+        let [r, g, b, a] = quarter;
+        [x, y, 0.0, r, g, b, a]
+    };
+    ctx.begin_default_pass(clear((0.0, 0.0, 0.0, 1.0)));
+    draw(
+        &ctx,
+        &pipeline,
+        &[
+            corner(-1.0, -1.0),
+            corner(1.0, -1.0),
+            corner(-1.0, 1.0),
+            corner(1.0, -1.0),
+            corner(1.0, 1.0),
+            corner(-1.0, 1.0),
+        ],
+    );
+    ctx.end_render_pass();
+    assert_eq!(pixels(&ctx, 4, 4), vec![[64, 0, 0, 255]; 16]);
+}
+
+#[test]
+fn alpha_blending() {
+    let ctx = Context::new_software(1, 1);
+    let shader = color_shader(&ctx);
+    let pipeline = pipeline(
+        &ctx,
+        &shader,
+        PipelineState {
+            blend: Some(BlendState {
+                op: BlendOp::Add,
+                source: BlendFactor::SourceAlpha,
+                dest: BlendFactor::OneMinusSourceAlpha,
+            }),
+            alpha_blend: Some(BlendState {
+                op: BlendOp::Add,
+                source: BlendFactor::One,
+                dest: BlendFactor::Zero,
+            }),
+            ..Default::default()
+        },
+    );
+
+    ctx.begin_default_pass(clear((0.0, 0.0, 1.0, 1.0)));
+    draw(&ctx, &pipeline, &fullscreen(0.0, [1.0, 0.0, 0.0, 0.25]));
+    ctx.end_render_pass();
+
+    // 0.25 * 255 and 0.75 * 255, rounded to nearest.
+    assert_eq!(pixels(&ctx, 1, 1), vec![[64, 0, 191, 64]]);
+}
+
+#[test]
+fn depth_test_keeps_nearest_fragment() {
+    let ctx = Context::new_software(1, 1);
+    let shader = color_shader(&ctx);
+    let pipeline = pipeline(
+        &ctx,
+        &shader,
+        PipelineState {
+            depth: DepthState {
+                compare: CompareFunc::Less,
+                write: true,
+            },
+            ..Default::default()
+        },
+    );
+
+    ctx.begin_default_pass(clear((0.0, 0.0, 0.0, 1.0)));
+    draw(&ctx, &pipeline, &fullscreen(0.0, [1.0, 0.0, 0.0, 1.0]));
+    draw(&ctx, &pipeline, &fullscreen(0.5, [0.0, 1.0, 0.0, 1.0]));
+    assert_eq!(pixels(&ctx, 1, 1), vec![RED]);
+    // An equal depth fails the test as well.
+    draw(&ctx, &pipeline, &fullscreen(0.0, [0.0, 1.0, 0.0, 1.0]));
+    assert_eq!(pixels(&ctx, 1, 1), vec![RED]);
+    draw(&ctx, &pipeline, &fullscreen(-0.5, [0.0, 0.0, 1.0, 1.0]));
+    ctx.end_render_pass();
+    assert_eq!(pixels(&ctx, 1, 1), vec![BLUE]);
+}
+
+#[test]
+fn stencil_masks_later_draws() {
+    let ctx = Context::new_software(4, 1);
+    let shader = color_shader(&ctx);
+    let stencil = |compare, pass_op| {
+        let face = StencilFaceState {
+            compare,
+            pass_op,
+            ..Default::default()
+        };
+        PipelineState {
+            stencil: Some(StencilState {
+                front: face,
+                back: face,
+                reference: 1,
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    };
+    let write = pipeline(
+        &ctx,
+        &shader,
+        stencil(CompareFunc::Always, StencilOp::Replace),
+    );
+    let test = pipeline(&ctx, &shader, stencil(CompareFunc::Equal, StencilOp::Keep));
+    let red = [1.0, 0.0, 0.0, 1.0];
+    let green = [0.0, 1.0, 0.0, 1.0];
+
+    ctx.begin_default_pass(clear((0.0, 0.0, 0.0, 1.0)));
+    // Marks the left half of the target.
+    draw(
+        &ctx,
+        &write,
+        &[
+            [-1.0, -1.0, 0.0, 1.0, 0.0, 0.0, 1.0],
+            [0.0, -1.0, 0.0, 1.0, 0.0, 0.0, 1.0],
+            [-1.0, 3.0, 0.0, 1.0, 0.0, 0.0, 1.0],
+            [0.0, -1.0, 0.0, 1.0, 0.0, 0.0, 1.0],
+            [0.0, 3.0, 0.0, 1.0, 0.0, 0.0, 1.0],
+            [-1.0, 3.0, 0.0, 1.0, 0.0, 0.0, 1.0],
+        ],
+    );
+    draw(&ctx, &test, &fullscreen(0.0, green));
+    ctx.end_render_pass();
+    assert_eq!(pixels(&ctx, 4, 1), vec![GREEN, GREEN, BLACK, BLACK]);
+
+    // Stencil values persist until cleared.
+    ctx.begin_default_pass(PassAction::Nothing);
+    draw(&ctx, &test, &fullscreen(0.0, red));
+    ctx.end_render_pass();
+    assert_eq!(pixels(&ctx, 4, 1), vec![RED, RED, BLACK, BLACK]);
+}
+
+#[test]
+fn stencil_depth_fail_op_applies_without_writing_color() {
+    let ctx = Context::new_software(1, 1);
+    let shader = color_shader(&ctx);
+    let face = StencilFaceState {
+        depth_fail_op: StencilOp::IncrementClamp,
+        ..Default::default()
+    };
+    let occluded = pipeline(
+        &ctx,
+        &shader,
+        PipelineState {
+            depth: DepthState {
+                compare: CompareFunc::Less,
+                write: true,
+            },
+            stencil: Some(StencilState {
+                front: face,
+                back: face,
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+    );
+    let face = StencilFaceState {
+        compare: CompareFunc::Equal,
+        ..Default::default()
+    };
+    let test = pipeline(
+        &ctx,
+        &shader,
+        PipelineState {
+            stencil: Some(StencilState {
+                front: face,
+                back: face,
+                reference: 2,
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+    );
+
+    ctx.begin_default_pass(clear((0.0, 0.0, 0.0, 1.0)));
+    draw(&ctx, &occluded, &fullscreen(0.0, [1.0, 0.0, 0.0, 1.0]));
+    // Both fail the depth test, incrementing the stencil value to 2 while
+    // keeping the depth value of the first draw.
+    draw(&ctx, &occluded, &fullscreen(0.5, [0.0, 1.0, 0.0, 1.0]));
+    draw(&ctx, &occluded, &fullscreen(0.5, [0.0, 1.0, 0.0, 1.0]));
+    assert_eq!(pixels(&ctx, 1, 1), vec![RED]);
+    draw(&ctx, &test, &fullscreen(0.0, [0.0, 0.0, 1.0, 1.0]));
+    ctx.end_render_pass();
+    assert_eq!(pixels(&ctx, 1, 1), vec![BLUE]);
+}
+
+#[test]
+fn nearest_and_linear_sampling() {
+    let ctx = Context::new_software(4, 1);
+    let image = ctx.create_image_with_data(
+        ImageKind::D2,
+        2,
+        1,
+        ImageFormat::Rgba8,
+        1,
+        &[0, 0, 0, 255, 255, 255, 255, 255],
+    );
+    let shader = texture_shader(&ctx, |input, [x, _]| input.sample(0, [x / 4.0, 0.5]));
+    let pipeline = pipeline(&ctx, &shader, PipelineState::default());
+
+    let mut rows = Vec::new();
+    for filter in [ImageFilter::Nearest, ImageFilter::Linear] {
+        let sampler = ctx.create_sampler(SamplerState {
+            min_filter: filter,
+            mag_filter: filter,
+            wrap_u: ImageWrap::Clamp,
+            wrap_v: ImageWrap::Clamp,
+            ..Default::default()
+        });
+        ctx.begin_default_pass(PassAction::Nothing);
+        ctx.set_pipeline(&pipeline);
+        ctx.set_images(&[(&image, &sampler)]);
+        draw(&ctx, &pipeline, &fullscreen(0.0, [0.0; 4]));
+        ctx.end_render_pass();
+        rows.push(
+            pixels(&ctx, 4, 1)
+                .iter()
+                .map(|pixel| pixel[0])
+                .collect::<Vec<_>>(),
+        );
+    }
+
+    assert_eq!(rows[0], [0, 0, 255, 255]);
+    // Texel centers are at u = 0.25 and 0.75, and the edges are clamped.
+    assert_eq!(rows[1], [0, 64, 191, 255]);
+}
+
+#[test]
+fn explicit_lod_selects_mip_levels() {
+    let ctx = Context::new_software(5, 1);
+    let image = ctx.create_image(ImageKind::D2, 4, 4, ImageFormat::Rgba8, 3);
+    for (level, color) in [RED, GREEN, BLUE].iter().enumerate() {
+        let size = 4 >> level;
+        image.update_level(level as u32, &color.repeat(size * size));
+    }
+    const LODS: [f32; 5] = [0.0, 1.0, 1.4, 1.6, 5.0];
+    let shader = texture_shader(&ctx, |input, [x, _]| {
+        input.sample_lod(0, [0.5, 0.5], LODS[x as usize])
+    });
+    let pipeline = pipeline(&ctx, &shader, PipelineState::default());
+
+    let mut rows = Vec::new();
+    for mipmap_filter in [ImageFilter::Nearest, ImageFilter::Linear] {
+        let sampler = ctx.create_sampler(SamplerState {
+            min_filter: ImageFilter::Nearest,
+            mag_filter: ImageFilter::Nearest,
+            mipmap_filter: Some(mipmap_filter),
+            ..Default::default()
+        });
+        ctx.begin_default_pass(PassAction::Nothing);
+        ctx.set_pipeline(&pipeline);
+        ctx.set_images(&[(&image, &sampler)]);
+        draw(&ctx, &pipeline, &fullscreen(0.0, [0.0; 4]));
+        ctx.end_render_pass();
+        rows.push(pixels(&ctx, 5, 1));
+    }
+
+    assert_eq!(rows[0], vec![RED, GREEN, GREEN, BLUE, BLUE]);
+    // 0.4 and 0.6 of the way from green to blue, and clamped to the last
+    // level.
+    assert_eq!(
+        rows[1],
+        vec![RED, GREEN, [0, 153, 102, 255], [0, 102, 153, 255], BLUE]
+    );
+}
+
+#[test]
+fn half_float_targets_round_to_nearest() {
+    let ctx = Context::new_software(1, 1);
+    let image = ctx.create_image(ImageKind::D2, 1, 1, ImageFormat::Rgba16F, 1);
+    let pass = ctx.create_render_pass(&[&image], None);
+    let shader = color_shader(&ctx);
+    let pipeline = pipeline(&ctx, &shader, PipelineState::default());
+
+    let render = |color| {
+        ctx.begin_pass(&pass, PassAction::Nothing);
+        draw(&ctx, &pipeline, &fullscreen(0.0, color));
+        ctx.end_render_pass();
+
+        let mut buf = [0; 8];
+        image.read(&mut buf);
+        buf.chunks_exact(2)
+            .map(|bytes| u16::from_ne_bytes([bytes[0], bytes[1]]))
+            .collect::<Vec<_>>()
+    };
+
+    // 1 + 2^-11 is halfway between two half floats and rounds to even.
+    assert_eq!(
+        render([0.5, -2.0, 1e6, 1.0 + 1.0 / 2048.0]),
+        [0x3800, 0xc000, 0x7c00, 0x3c00]
+    );
+    // Ties round to even for subnormals, and 65520 rounds up to infinity.
+    let subnormal = 1.0 / (1 << 25) as f32;
+    assert_eq!(
+        render([subnormal, 3.0 * subnormal, 65520.0, -0.0]),
+        [0x0000, 0x0002, 0x7c00, 0x8000]
+    );
+
+    // Sampling converts back to single precision.
+    let source = ctx.create_image_with_data(
+        ImageKind::D2,
+        1,
+        1,
+        ImageFormat::Rgba16F,
+        1,
+        &[0x3400u16, 0x3800, 0x3a00, 0x3c00]
+            .iter()
+            .flat_map(|half| half.to_ne_bytes())
+            .collect::<Vec<_>>(),
+    );
+    let sampler = ctx.create_sampler(SamplerState::default());
+    let shader = texture_shader(&ctx, |input, _| input.sample(0, [0.5, 0.5]));
+    let pipeline = self::pipeline(&ctx, &shader, PipelineState::default());
+    ctx.begin_default_pass(PassAction::Nothing);
+    ctx.set_pipeline(&pipeline);
+    ctx.set_images(&[(&source, &sampler)]);
+    draw(&ctx, &pipeline, &fullscreen(0.0, [0.0; 4]));
+    ctx.end_render_pass();
+    assert_eq!(pixels(&ctx, 1, 1), vec![[64, 128, 191, 255]]);
+}
+
+#[test]
+fn srgb_targets_and_images_convert_color_channels() {
+    let ctx = Context::new_software(1, 1);
+    let image = ctx.create_image(ImageKind::D2, 1, 1, ImageFormat::Srgba8, 1);
+    let pass = ctx.create_render_pass(&[&image], None);
+    let shader = color_shader(&ctx);
+    let pipeline = pipeline(&ctx, &shader, PipelineState::default());
+
+    ctx.begin_pass(&pass, PassAction::Nothing);
+    draw(&ctx, &pipeline, &fullscreen(0.0, [0.5, 0.0, 1.0, 0.5]));
+    ctx.end_render_pass();
+
+    // Alpha is stored linearly.
+    let mut buf = [0; 4];
+    image.read(&mut buf);
+    assert_eq!(buf, [188, 0, 255, 128]);
+
+    let sampler = ctx.create_sampler(SamplerState::default());
+    let shader = texture_shader(&ctx, |input, _| input.sample(0, [0.5, 0.5]));
+    let pipeline = self::pipeline(&ctx, &shader, PipelineState::default());
+    ctx.begin_default_pass(PassAction::Nothing);
+    ctx.set_pipeline(&pipeline);
+    ctx.set_images(&[(&image, &sampler)]);
+    draw(&ctx, &pipeline, &fullscreen(0.0, [0.0; 4]));
+    ctx.end_render_pass();
+    assert_eq!(pixels(&ctx, 1, 1), vec![[128, 0, 255, 128]]);
+}
+
+#[test]
+fn render_pass_outlives_dropped_attachment() {
+    let ctx = Context::new_software(1, 1);
+    let image = ctx.create_image(ImageKind::D2, 1, 1, ImageFormat::Rgba8, 1);
+    let pass = ctx.create_render_pass(&[&image], None);
+    drop(image);
+    let other = ctx.create_image_with_data(ImageKind::D2, 1, 1, ImageFormat::Rgba8, 1, &BLACK);
+    let shader = color_shader(&ctx);
+    let pipeline = pipeline(&ctx, &shader, PipelineState::default());
+
+    ctx.begin_pass(&pass, clear((0.0, 0.0, 1.0, 1.0)));
+    draw(&ctx, &pipeline, &fullscreen(0.0, [1.0, 0.0, 0.0, 1.0]));
+    assert_eq!(pixels(&ctx, 1, 1), vec![RED]);
+    ctx.end_render_pass();
+
+    let mut buf = [0; 4];
+    other.read(&mut buf);
+    assert_eq!(buf, BLACK);
+}